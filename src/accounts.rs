@@ -0,0 +1,234 @@
+//! Store of sessions of multiple accounts, keyed by student number.
+
+use anyhow::{Context, Result, bail};
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+use ucas_iclass::UserSessionInfo;
+
+/// Directory of the store used when none is set.
+pub const DEFAULT_ACCOUNTS_DIR: &str = "accounts";
+/// Name of the file in the store holding the student number of the default account.
+const DEFAULT_FILE: &str = "default";
+
+/// A directory of session files named after student numbers, like `accounts/2024xxxx.json`, so that each works as a regular session file.
+pub struct AccountStore {
+    /// The directory.
+    dir: PathBuf,
+}
+
+impl AccountStore {
+    /// Creates a store in given directory, which is created when the first account is added.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The directory of the store.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Path of the session file of given account, which must be stored.
+    pub fn session_file(&self, student_no: &str) -> Result<PathBuf> {
+        let path = self.path(student_no)?;
+        if !path.is_file() {
+            bail!(
+                "Account {student_no} not found in {}, add it with `ucas-iclass accounts add`",
+                self.dir.display()
+            );
+        }
+        Ok(path)
+    }
+
+    /// Loads the sessions of all stored accounts, ordered by student number.
+    pub fn list(&self) -> Result<Vec<UserSessionInfo>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut sessions = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "json")
+            {
+                let session = UserSessionInfo::load_from_file(&path)
+                    .with_context(|| format!("Invalid session file {}", path.display()))?;
+                sessions.push(session);
+            }
+        }
+        sessions.sort_by(|a, b| a.student_no.cmp(&b.student_no));
+        Ok(sessions)
+    }
+
+    /// Saves the session of an account, replacing the stored one if any, and makes it the default if there is none. Returns the path of its session file.
+    pub fn add(&self, session: &UserSessionInfo) -> Result<PathBuf> {
+        let path = self.path(&session.student_no)?;
+        fs::create_dir_all(&self.dir)?;
+        session.save_to_file(&path)?;
+        if self.default_account()?.is_none() {
+            self.set_default(&session.student_no)?;
+        }
+        Ok(path)
+    }
+
+    /// Removes a stored account, unsetting the default if it was.
+    pub fn remove(&self, student_no: &str) -> Result<()> {
+        let path = self.session_file(student_no)?;
+        let was_default = self.default_account()?.as_deref() == Some(student_no);
+        fs::remove_file(path)?;
+        if was_default {
+            fs::remove_file(self.dir.join(DEFAULT_FILE))?;
+        }
+        Ok(())
+    }
+
+    /// Student number of the default account, if set and still stored.
+    pub fn default_account(&self) -> Result<Option<String>> {
+        let student_no = match fs::read_to_string(self.dir.join(DEFAULT_FILE)) {
+            Ok(content) => content.trim().to_string(),
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let stored = self.path(&student_no).is_ok_and(|path| path.is_file());
+        Ok(stored.then_some(student_no))
+    }
+
+    /// Makes a stored account the default.
+    pub fn set_default(&self, student_no: &str) -> Result<()> {
+        self.session_file(student_no)?;
+        fs::write(self.dir.join(DEFAULT_FILE), format!("{student_no}\n"))?;
+        Ok(())
+    }
+
+    /// Path of the session file of given account, rejecting student numbers that are not plain file names.
+    fn path(&self, student_no: &str) -> Result<PathBuf> {
+        if student_no.is_empty() || !student_no.chars().all(|c| c.is_ascii_alphanumeric()) {
+            bail!("Invalid student number: {student_no:?}");
+        }
+        Ok(self.dir.join(format!("{student_no}.json")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AccountStore, DEFAULT_FILE};
+    use std::{env, fs, process};
+    use ucas_iclass::UserSessionInfo;
+
+    /// A store in a fresh temporary directory, removed when dropped.
+    struct TempStore(AccountStore);
+
+    impl TempStore {
+        fn new(name: &str) -> Self {
+            let dir = env::temp_dir().join(format!("ucas-iclass-{}-{name}", process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            Self(AccountStore::new(dir))
+        }
+    }
+
+    impl Drop for TempStore {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(self.0.dir());
+        }
+    }
+
+    fn session(student_no: &str) -> UserSessionInfo {
+        UserSessionInfo {
+            id: format!("id-{student_no}"),
+            session_id: format!("session-{student_no}"),
+            real_name: "张三".to_string(),
+            student_no: student_no.to_string(),
+        }
+    }
+
+    fn student_nos(store: &AccountStore) -> Vec<String> {
+        store
+            .list()
+            .unwrap()
+            .into_iter()
+            .map(|session| session.student_no)
+            .collect()
+    }
+
+    #[test]
+    fn add_and_list() {
+        let store = TempStore::new("add-and-list");
+        let store = &store.0;
+        assert!(student_nos(store).is_empty());
+        assert_eq!(store.default_account().unwrap(), None);
+
+        let path = store.add(&session("2025002")).unwrap();
+        assert_eq!(path, store.dir().join("2025002.json"));
+        store.add(&session("2025001")).unwrap();
+        assert_eq!(student_nos(store), ["2025001", "2025002"]);
+        // The first account added becomes the default
+        assert_eq!(store.default_account().unwrap().as_deref(), Some("2025002"));
+
+        // Adding again replaces the session
+        let mut renewed = session("2025001");
+        renewed.session_id = "renewed".to_string();
+        store.add(&renewed).unwrap();
+        let sessions = store.list().unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].session_id, "renewed");
+    }
+
+    #[test]
+    fn set_default_and_remove() {
+        let store = TempStore::new("set-default-and-remove");
+        let store = &store.0;
+        store.add(&session("2025001")).unwrap();
+        store.add(&session("2025002")).unwrap();
+
+        store.set_default("2025002").unwrap();
+        assert_eq!(store.default_account().unwrap().as_deref(), Some("2025002"));
+        assert!(store.set_default("2025003").is_err());
+        assert_eq!(store.default_account().unwrap().as_deref(), Some("2025002"));
+
+        // Removing another account keeps the default
+        store.remove("2025001").unwrap();
+        assert_eq!(store.default_account().unwrap().as_deref(), Some("2025002"));
+        assert!(store.session_file("2025001").is_err());
+        assert!(store.remove("2025001").is_err());
+
+        // Removing the default unsets it
+        store.remove("2025002").unwrap();
+        assert_eq!(store.default_account().unwrap(), None);
+        assert!(!store.dir().join(DEFAULT_FILE).exists());
+        assert!(student_nos(store).is_empty());
+    }
+
+    #[test]
+    fn stale_default_is_ignored() {
+        let store = TempStore::new("stale-default");
+        let store = &store.0;
+        store.add(&session("2025001")).unwrap();
+        fs::remove_file(store.dir().join("2025001.json")).unwrap();
+        assert_eq!(store.default_account().unwrap(), None);
+        // The next account added becomes the default
+        store.add(&session("2025002")).unwrap();
+        assert_eq!(store.default_account().unwrap().as_deref(), Some("2025002"));
+    }
+
+    #[test]
+    fn invalid_student_numbers() {
+        let store = TempStore::new("invalid-student-numbers");
+        let store = &store.0;
+        for student_no in ["", "../evil", "a/b", "2025 001", "default.json"] {
+            assert!(
+                store.add(&session(student_no)).is_err(),
+                "{student_no:?} should be rejected"
+            );
+            assert!(store.session_file(student_no).is_err());
+        }
+        // Non-session files in the directory are skipped
+        fs::create_dir_all(store.dir()).unwrap();
+        fs::write(store.dir().join("notes.txt"), "hello").unwrap();
+        assert!(student_nos(store).is_empty());
+    }
+}
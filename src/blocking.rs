@@ -0,0 +1,232 @@
+//! Blocking (synchronous) API.
+//!
+//! [`IClass`] wraps the asynchronous [`IClass`](super::IClass) together with a `compio` runtime, and runs each request to completion on the current thread, so that no async runtime is needed on the caller side.
+
+use super::{
+    CheckInPolicy, CheckInResult, CheckInTarget, Course, DailySchedule, IClass as AsyncIClass,
+    IClassError, Schedule, ScheduleId, ScheduleUuid, Semester,
+};
+use chrono::NaiveDate;
+use compio::runtime::Runtime;
+use futures_util::TryStreamExt;
+use url::Url;
+
+/// Blocking counterpart of [`IClass`](super::IClass), with the same return types and errors.
+pub struct IClass {
+    /// The asynchronous instance.
+    inner: AsyncIClass,
+    /// The runtime driving requests.
+    runtime: Runtime,
+}
+
+impl IClass {
+    /// Creates a new instance of [`IClass`].
+    ///
+    /// # Errors
+    ///
+    /// Errors of the transport if the TLS backend fails to initialize, or [`IClassError::IoError`] if the runtime fails to start.
+    pub fn new() -> Result<Self, IClassError> {
+        Self::from_async(AsyncIClass::try_new()?)
+    }
+
+    /// Creates a new instance of [`IClass`] with given API root URL.
+    ///
+    /// # Errors
+    ///
+    /// Errors of the transport if the TLS backend fails to initialize, or [`IClassError::IoError`] if the runtime fails to start.
+    pub fn with_api_root(url: Url) -> Result<Self, IClassError> {
+        Self::from_async(AsyncIClass::try_with_api_root(url)?)
+    }
+
+    /// Wraps an asynchronous instance, for example one configured by [`IClassBuilder`](super::IClassBuilder).
+    ///
+    /// # Errors
+    ///
+    /// [`IClassError::IoError`] if the runtime fails to start.
+    pub fn from_async(inner: AsyncIClass) -> Result<Self, IClassError> {
+        Ok(Self {
+            inner,
+            runtime: Runtime::new()?,
+        })
+    }
+
+    /// Gets the asynchronous instance, for synchronous methods like [`restore_session_from_file`](super::IClass::restore_session_from_file).
+    #[must_use]
+    pub const fn inner(&self) -> &AsyncIClass {
+        &self.inner
+    }
+
+    /// Gets the asynchronous instance mutably, for setters like [`set_credential_provider`](super::IClass::set_credential_provider).
+    pub const fn inner_mut(&mut self) -> &mut AsyncIClass {
+        &mut self.inner
+    }
+
+    /// Unwraps the asynchronous instance.
+    #[must_use]
+    pub fn into_inner(self) -> AsyncIClass {
+        self.inner
+    }
+
+    /// Logs in to the iClass platform. See [`IClass::login`](super::IClass::login).
+    ///
+    /// # Errors
+    ///
+    /// See [`IClassError`].
+    pub fn login(&self, username: &str, password: &str) -> Result<(), IClassError> {
+        self.runtime.block_on(self.inner.login(username, password))
+    }
+
+    /// Queries semesters. See [`IClass::query_semester`](super::IClass::query_semester).
+    ///
+    /// # Errors
+    ///
+    /// See [`IClassError`].
+    pub fn query_semester(&self) -> Result<Vec<Semester>, IClassError> {
+        self.runtime.block_on(self.inner.query_semester())
+    }
+
+    /// Queries courses. See [`IClass::query_courses`](super::IClass::query_courses).
+    ///
+    /// # Errors
+    ///
+    /// See [`IClassError`].
+    pub fn query_courses(&self) -> Result<Vec<Course>, IClassError> {
+        self.runtime.block_on(self.inner.query_courses())
+    }
+
+    /// Queries the schedule on given date. See [`IClass::query_daily_schedule`](super::IClass::query_daily_schedule).
+    ///
+    /// # Errors
+    ///
+    /// See [`IClassError`].
+    pub fn query_daily_schedule(&self, date: &NaiveDate) -> Result<Vec<Schedule>, IClassError> {
+        self.runtime.block_on(self.inner.query_daily_schedule(date))
+    }
+
+    /// Queries the schedule of the week containing given date. See [`IClass::query_weekly_schedule`](super::IClass::query_weekly_schedule).
+    ///
+    /// # Errors
+    ///
+    /// See [`IClassError`].
+    pub fn query_weekly_schedule(
+        &self,
+        date: &NaiveDate,
+    ) -> Result<Vec<DailySchedule>, IClassError> {
+        self.runtime
+            .block_on(self.inner.query_weekly_schedule(date))
+    }
+
+    /// Queries the schedule of the week containing given date with check-in status. See [`IClass::query_weekly_schedule_with_status`](super::IClass::query_weekly_schedule_with_status).
+    ///
+    /// # Errors
+    ///
+    /// See [`IClassError`].
+    pub fn query_weekly_schedule_with_status(
+        &self,
+        date: &NaiveDate,
+    ) -> Result<Vec<DailySchedule>, IClassError> {
+        self.runtime
+            .block_on(self.inner.query_weekly_schedule_with_status(date))
+    }
+
+    /// Fills in unknown check-in status of given daily schedules. See [`IClass::fill_check_in_status`](super::IClass::fill_check_in_status).
+    ///
+    /// # Errors
+    ///
+    /// See [`IClassError`].
+    pub fn fill_check_in_status(
+        &self,
+        daily_schedules: &mut [DailySchedule],
+    ) -> Result<(), IClassError> {
+        self.runtime
+            .block_on(self.inner.fill_check_in_status(daily_schedules))
+    }
+
+    /// Queries schedules from `start` to `end` (both inclusive). See [`IClass::query_schedule_range`](super::IClass::query_schedule_range).
+    ///
+    /// # Errors
+    ///
+    /// See [`IClassError`].
+    pub fn query_schedule_range(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<Vec<DailySchedule>, IClassError> {
+        self.runtime
+            .block_on(self.inner.query_schedule_range(start, end).try_collect())
+    }
+
+    /// Queries schedules of given semester. See [`IClass::query_semester_schedule`](super::IClass::query_semester_schedule).
+    ///
+    /// # Errors
+    ///
+    /// See [`IClassError`].
+    pub fn query_semester_schedule(
+        &self,
+        semester: &Semester,
+    ) -> Result<Vec<DailySchedule>, IClassError> {
+        self.runtime
+            .block_on(self.inner.query_semester_schedule(semester).try_collect())
+    }
+
+    /// Checks in given schedule, by uuid or by id. See [`IClass::check_in`](super::IClass::check_in).
+    ///
+    /// # Errors
+    ///
+    /// See [`IClassError`].
+    pub fn check_in(
+        &self,
+        target: &CheckInTarget,
+        timestamp: u128,
+    ) -> Result<CheckInResult, IClassError> {
+        self.runtime
+            .block_on(self.inner.check_in(target, timestamp))
+    }
+
+    /// Checks in the schedule with given uuid. See [`IClass::check_in_by_uuid`](super::IClass::check_in_by_uuid).
+    ///
+    /// # Errors
+    ///
+    /// See [`IClassError`].
+    pub fn check_in_by_uuid(
+        &self,
+        schedule_uuid: &ScheduleUuid,
+        timestamp: u128,
+    ) -> Result<CheckInResult, IClassError> {
+        self.runtime
+            .block_on(self.inner.check_in_by_uuid(schedule_uuid, timestamp))
+    }
+
+    /// Checks in the schedule with given id. See [`IClass::check_in_by_id`](super::IClass::check_in_by_id).
+    ///
+    /// # Errors
+    ///
+    /// See [`IClassError`].
+    pub fn check_in_by_id(
+        &self,
+        schedule_id: &ScheduleId,
+        timestamp: u128,
+    ) -> Result<CheckInResult, IClassError> {
+        self.runtime
+            .block_on(self.inner.check_in_by_id(schedule_id, timestamp))
+    }
+
+    /// Queries the schedules eligible for check-in under given policy, ranked. See [`IClass::current_schedules`](super::IClass::current_schedules).
+    ///
+    /// # Errors
+    ///
+    /// See [`IClassError`].
+    pub fn current_schedules(&self, policy: &CheckInPolicy) -> Result<Vec<Schedule>, IClassError> {
+        self.runtime.block_on(self.inner.current_schedules(policy))
+    }
+
+    /// Checks in the schedule in given scanned QR code payload. See [`IClass::check_in_with_qr_payload`](super::IClass::check_in_with_qr_payload).
+    ///
+    /// # Errors
+    ///
+    /// See [`IClassError`].
+    pub fn check_in_with_qr_payload(&self, payload: &str) -> Result<CheckInResult, IClassError> {
+        self.runtime
+            .block_on(self.inner.check_in_with_qr_payload(payload))
+    }
+}
@@ -0,0 +1,209 @@
+//! Builder for configuring [`IClass`].
+
+use super::{
+    API_ROOT, Clock, CredentialProvider, IClass, UserSessionInfo,
+    transport::{Transport, TransportConfig},
+};
+#[cfg(any(feature = "cyper", feature = "reqwest"))]
+use super::{
+    IClassError,
+    transport::{DefaultTransport, HttpTransport},
+};
+use futures_util::lock::Mutex as AsyncMutex;
+use std::{path::PathBuf, sync::Mutex, time::Duration};
+use url::Url;
+
+/// A builder for [`IClass`], created by [`IClass::builder`].
+///
+/// Connection limits and pool sizes are not configurable: `cyper` does not expose its connection pool, so each transport keeps the defaults of its HTTP client.
+#[must_use]
+pub struct IClassBuilder {
+    /// API root URL.
+    api_root: Url,
+    /// Configuration of the transport.
+    transport: TransportConfig,
+    /// Pre-restored user session information.
+    user_session: Option<UserSessionInfo>,
+    /// Credential provider for logging in again.
+    credential_provider: Option<Box<dyn CredentialProvider>>,
+    /// File to save the session to after logging in again.
+    session_file: Option<PathBuf>,
+    /// Offset of the server clock to the local clock in milliseconds.
+    clock_offset: i64,
+    /// Source of current time.
+    clock: Option<Box<dyn Clock>>,
+}
+
+impl Default for IClassBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IClassBuilder {
+    /// Creates a new builder with default configuration.
+    #[allow(clippy::missing_panics_doc, reason = "URL is constant and valid")]
+    pub fn new() -> Self {
+        Self {
+            api_root: Url::parse(API_ROOT).unwrap(),
+            transport: TransportConfig::default(),
+            user_session: None,
+            credential_provider: None,
+            session_file: None,
+            clock_offset: 0,
+            clock: None,
+        }
+    }
+
+    /// Sets the API root URL, defaulting to [`API_ROOT`].
+    pub fn api_root(mut self, url: Url) -> Self {
+        self.api_root = url;
+        self
+    }
+
+    /// Sets the timeout for each request, including reading the response. No timeout by default.
+    pub const fn timeout(mut self, timeout: Duration) -> Self {
+        self.transport.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the `User-Agent` header.
+    pub fn user_agent<S: Into<String>>(mut self, user_agent: S) -> Self {
+        self.transport.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Adds an extra header sent with every request.
+    pub fn header<K: Into<String>, V: Into<String>>(mut self, name: K, value: V) -> Self {
+        self.transport.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Sets the proxy for all requests, overriding proxies from environment variables.
+    ///
+    /// Supported schemes are `http`, `https`, `socks4`, `socks4a`, `socks5` and `socks5h`, where `socks4a` and `socks5h` resolve host names on the proxy. Credentials may be embedded in the URL or set with [`proxy_auth`](Self::proxy_auth).
+    ///
+    /// Without this, proxies are taken from `ALL_PROXY`, `HTTPS_PROXY`, `HTTP_PROXY` and `NO_PROXY` environment variables, unless disabled by [`system_proxy`](Self::system_proxy).
+    pub fn proxy(mut self, url: Url) -> Self {
+        self.transport.proxy = Some(url);
+        self
+    }
+
+    /// Sets the username and password for the proxy.
+    pub fn proxy_auth<U: Into<String>, P: Into<String>>(
+        mut self,
+        username: U,
+        password: P,
+    ) -> Self {
+        self.transport.proxy_auth = Some((username.into(), password.into()));
+        self
+    }
+
+    /// Sets hosts that bypass the proxy set by [`proxy`](Self::proxy), as a comma-separated list of domains (matching subdomains too), IP addresses or subnets like `192.168.1.0/24`, or `*` for all hosts. Defaults to `NO_PROXY` environment variable.
+    pub fn no_proxy<S: Into<String>>(mut self, hosts: S) -> Self {
+        self.transport.no_proxy = Some(hosts.into());
+        self
+    }
+
+    /// Sets whether to use proxies from environment variables if no proxy is set. Enabled by default.
+    pub const fn system_proxy(mut self, enabled: bool) -> Self {
+        self.transport.system_proxy = enabled;
+        self
+    }
+
+    /// Adds trusted root certificates in PEM format, in addition to the platform roots. Useful for servers using self-signed or private-CA certificates.
+    pub fn root_certificates_pem<B: Into<Vec<u8>>>(mut self, pem: B) -> Self {
+        self.transport.root_certificates.push(pem.into());
+        self
+    }
+
+    /// Sets the client certificate chain (leaf first) and private key in PEM format, for servers requiring client authentication.
+    pub fn client_identity_pem<C: Into<Vec<u8>>, K: Into<Vec<u8>>>(
+        mut self,
+        cert_chain: C,
+        key: K,
+    ) -> Self {
+        self.transport.client_identity = Some((cert_chain.into(), key.into()));
+        self
+    }
+
+    /// Sets whether to accept invalid server certificates, including expired, self-signed and mismatched ones. Disabled by default.
+    ///
+    /// # Warning
+    ///
+    /// This makes connections vulnerable to man-in-the-middle attacks. Only use it for development against servers you control; prefer [`root_certificates_pem`](Self::root_certificates_pem) otherwise.
+    pub const fn danger_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.transport.accept_invalid_certs = accept;
+        self
+    }
+
+    /// Sets the pre-restored user session information.
+    pub fn user_session(mut self, session: UserSessionInfo) -> Self {
+        self.user_session = Some(session);
+        self
+    }
+
+    /// Sets the credential provider. See [`IClass::set_credential_provider`].
+    pub fn credential_provider<P>(mut self, provider: P) -> Self
+    where
+        P: CredentialProvider + 'static,
+    {
+        self.credential_provider = Some(Box::new(provider));
+        self
+    }
+
+    /// Sets the session file. See [`IClass::set_session_file`].
+    pub fn session_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.session_file = Some(path.into());
+        self
+    }
+
+    /// Sets the server clock offset in milliseconds. See [`IClass::set_clock_offset`].
+    pub const fn clock_offset(mut self, offset_millis: i64) -> Self {
+        self.clock_offset = offset_millis;
+        self
+    }
+
+    /// Sets the source of current time. See [`IClass::set_clock`].
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Some(Box::new(clock));
+        self
+    }
+
+    /// Builds the [`IClass`] instance with the [`DefaultTransport`].
+    ///
+    /// # Errors
+    ///
+    /// See [`build_with`](Self::build_with).
+    #[cfg(any(feature = "cyper", feature = "reqwest"))]
+    pub fn build(self) -> Result<IClass, IClassError> {
+        self.build_with::<DefaultTransport>()
+    }
+
+    /// Builds the [`IClass`] instance with given type of [`HttpTransport`], like `build_with::<ReqwestTransport>()`.
+    ///
+    /// # Errors
+    ///
+    /// - [`IClassError::HttpError`] if the user agent or any header is invalid.
+    /// - [`IClassError::IoError`] if any PEM data is invalid or contains no certificates.
+    /// - Errors of the transport if the proxy URL is invalid, or the TLS backend fails to initialize.
+    #[cfg(any(feature = "cyper", feature = "reqwest"))]
+    pub fn build_with<T: HttpTransport>(self) -> Result<IClass<T>, IClassError> {
+        let transport = T::from_config(&self.transport)?;
+        Ok(self.build_with_transport(transport))
+    }
+
+    /// Builds the [`IClass`] instance with given transport, ignoring transport-related options like timeout, headers, proxy and TLS.
+    pub fn build_with_transport<T: Transport>(self, transport: T) -> IClass<T> {
+        IClass {
+            api_root: self.api_root,
+            transport,
+            user_session: Mutex::new(self.user_session),
+            credential_provider: self.credential_provider,
+            relogin_lock: AsyncMutex::new(()),
+            session_file: self.session_file,
+            clock_offset: self.clock_offset,
+            clock: self.clock,
+        }
+    }
+}
@@ -1,25 +1,48 @@
 //! Check in to given class.
 
-use super::{IClass, IClassError, Response};
-use serde::Deserialize;
+use super::{
+    CheckInTarget, IClass, IClassError, QrPayload, ScheduleId, ScheduleUuid,
+    transport::{Transport, TransportRequest},
+};
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
 /// Check-in response structure.
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct CheckInResult {
     // Object {"stuSignId": String("41254913"), "stuSignStatus": String("1")}
     /// Check-in ID. Maybe the serial number of the check-in record.
-    #[serde(rename = "stuSignId")]
+    #[serde(rename(deserialize = "stuSignId"))]
     pub id: String,
     /// Check-in status.
     #[serde(
-        rename = "stuSignStatus",
+        rename(deserialize = "stuSignStatus"),
         deserialize_with = "super::util::deserialize_str_to_bool"
     )]
     pub status: bool,
 }
 
-impl IClass {
+#[allow(
+    clippy::future_not_send,
+    reason = "`Send` whenever the transport is, see `Transport`"
+)]
+impl<T: Transport> IClass<T> {
+    /// Checks in given schedule, by uuid or by id. See [`check_in_by_uuid`](Self::check_in_by_uuid) and [`check_in_by_id`](Self::check_in_by_id).
+    ///
+    /// # Errors
+    ///
+    /// See [`IClassError`].
+    pub async fn check_in(
+        &self,
+        target: &CheckInTarget,
+        timestamp: u128,
+    ) -> Result<CheckInResult, IClassError> {
+        match target {
+            CheckInTarget::Uuid(uuid) => self.check_in_by_uuid(uuid, timestamp).await,
+            CheckInTarget::Id(id) => self.check_in_by_id(id, timestamp).await,
+        }
+    }
+
     /// Checks in the schedule with given uuid. This is equivalent to scanning the QR code on the smart device outside the classroom.
     ///
     /// # Errors
@@ -31,26 +54,22 @@ impl IClass {
     /// This function will panic if system time is before [`UNIX_EPOCH`].
     pub async fn check_in_by_uuid(
         &self,
-        schedule_uuid: &str,
+        schedule_uuid: &ScheduleUuid,
         timestamp: u128,
     ) -> Result<CheckInResult, IClassError> {
         // /app/course/stu_scan_sign.action?timeTableId={schedule_uuid}&timestamp={timestamp}
-        let user_session = self.get_user_session()?;
         let url = self.api_root.join("app/course/stu_scan_sign.action")?;
-        let response: Response<CheckInResult> = self
-            .client
-            .get(url)? // TODO: Maybe post + form?
-            .header("sessionId", &user_session.session_id)?
-            .query(&[
-                ("timeTableId", schedule_uuid),
-                ("timestamp", &timestamp.to_string()),
-                ("id", user_session.id.as_str()),
-            ])?
-            .send()
-            .await?
-            .json()
+        let check_in_result = self
+            .with_session(|user_session| {
+                TransportRequest::get(url.clone()) // TODO: Maybe post + form?
+                    .header("sessionId", &user_session.session_id)
+                    .query(&[
+                        ("timeTableId", schedule_uuid.as_str()),
+                        ("timestamp", &timestamp.to_string()),
+                        ("id", user_session.id.as_str()),
+                    ])
+            })
             .await?;
-        let check_in_result = response.into_result()?;
 
         Ok(check_in_result)
     }
@@ -66,29 +85,39 @@ impl IClass {
     /// This function will panic if system time is before [`UNIX_EPOCH`].
     pub async fn check_in_by_id(
         &self,
-        schedule_id: &str,
+        schedule_id: &ScheduleId,
         timestamp: u128,
     ) -> Result<CheckInResult, IClassError> {
         // /app/course/stu_sign_in.action?scheduleId={schedule_id}&timestamp={timestamp}
-        let user_session = self.get_user_session()?;
         let url = self.api_root.join("app/course/stu_scan_sign.action")?;
-        let response: Response<CheckInResult> = self
-            .client
-            .get(url)? // TODO: Maybe post + form?
-            .header("sessionId", &user_session.session_id)?
-            .query(&[
-                ("courseSchedId", schedule_id),
-                ("timestamp", &timestamp.to_string()),
-                ("id", user_session.id.as_str()),
-            ])?
-            .send()
-            .await?
-            .json()
+        let check_in_result = self
+            .with_session(|user_session| {
+                TransportRequest::get(url.clone()) // TODO: Maybe post + form?
+                    .header("sessionId", &user_session.session_id)
+                    .query(&[
+                        ("courseSchedId", schedule_id.as_str()),
+                        ("timestamp", &timestamp.to_string()),
+                        ("id", user_session.id.as_str()),
+                    ])
+            })
             .await?;
-        let check_in_result = response.into_result()?;
 
         Ok(check_in_result)
     }
+
+    /// Checks in the schedule in given scanned QR code payload, see [`QrPayload`] for accepted formats. The timestamp embedded in the payload is used if any, or [`server_timestamp_millis`](Self::server_timestamp_millis) otherwise.
+    ///
+    /// # Errors
+    ///
+    /// [`IClassError::InvalidQrPayload`] if the payload is not recognized. See [`IClassError`] for others.
+    pub async fn check_in_with_qr_payload(
+        &self,
+        payload: &str,
+    ) -> Result<CheckInResult, IClassError> {
+        let QrPayload { target, timestamp } = payload.parse()?;
+        let timestamp = timestamp.unwrap_or_else(|| self.server_timestamp_millis());
+        self.check_in(&target, timestamp).await
+    }
 }
 
 impl fmt::Display for CheckInResult {
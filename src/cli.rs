@@ -2,17 +2,75 @@
 
 use std::str::FromStr;
 
+use super::{config::Key, output::Format};
 use argh::FromArgs;
 use chrono::NaiveDate;
-use ucas_iclass::util::{current_timestamp_millis, get_today};
 use url::Url;
 
+/// Environment variable for the username used to log in again automatically.
+pub const USERNAME_ENV: &str = "UCAS_ICLASS_USERNAME";
+/// Environment variable for the password used to log in, and to log in again automatically.
+pub const PASSWORD_ENV: &str = "UCAS_ICLASS_PASSWORD";
+
 /// iClass API for UCAS.
 #[derive(Clone, Debug, FromArgs)]
+#[argh(
+    error_code(1, "other errors"),
+    error_code(10, "not logged in, session expired, or credentials unavailable"),
+    error_code(
+        11,
+        "invalid parameter (ERRCODE 100), invalid schedule id or uuid, unrecognized QR code payload or image"
+    ),
+    error_code(12, "user not found (ERRCODE 106)"),
+    error_code(13, "wrong password (ERRCODE 107)"),
+    error_code(14, "QR code expired"),
+    error_code(15, "already checked in"),
+    error_code(16, "other API errors"),
+    error_code(17, "network, TLS, proxy authentication or data parsing errors")
+)]
 pub struct Cli {
     /// API root URL, defaulting to UCAS iClass API root
     #[argh(option, short = 'a')]
     pub api_root: Option<Url>,
+    /// timeout for each request in seconds, no timeout by default
+    #[argh(option)]
+    pub timeout: Option<u64>,
+    /// the User-Agent header to send
+    #[argh(option)]
+    pub user_agent: Option<String>,
+    /// extra header to send with every request in "Name: value" format, can be repeated
+    #[argh(option, short = 'H')]
+    pub header: Vec<String>,
+    /// proxy URL for all requests with http, https, socks5 or socks5h scheme and optional credentials, defaulting to proxy environment variables
+    #[argh(option)]
+    pub proxy: Option<Url>,
+    /// comma-separated hosts that bypass the proxy, defaulting to the no-proxy environment variable
+    #[argh(option)]
+    pub no_proxy: Option<String>,
+    /// connect directly, ignoring proxy environment variables
+    #[argh(switch)]
+    pub direct: bool,
+    /// PEM file of extra root certificates to trust, can be repeated
+    #[argh(option)]
+    pub ca_cert: Vec<String>,
+    /// PEM file of the client certificate chain, requires --client-key
+    #[argh(option)]
+    pub client_cert: Option<String>,
+    /// PEM file of the client private key, requires --client-cert
+    #[argh(option)]
+    pub client_key: Option<String>,
+    /// accept invalid server certificates, dangerous and only meant for development
+    #[argh(switch)]
+    pub insecure: bool,
+    /// pretend the current time is given time in China Standard Time, like "2025-10-13 07:59:00", with the clock running from there; useful for testing
+    #[argh(option)]
+    pub now: Option<String>,
+    /// output format: text (default), json, jsonl, csv or table
+    #[argh(option)]
+    pub format: Option<Format>,
+    /// the profile in the config file to use, defaulting to "default"
+    #[argh(option)]
+    pub profile: Option<String>,
     /// the subcommand to run
     #[argh(subcommand)]
     pub subcommand: SubCommands,
@@ -24,12 +82,26 @@ pub struct Cli {
 pub enum SubCommands {
     /// Login to iClass and save session to a file.
     Login(Login),
+    /// List semesters, marking the current one.
+    Semester(Semester),
     /// List courses in current semester.
     Courses(Courses),
     /// Get schedule for a specific date or week, defaulting to today.
     Schedule(Schedule),
     /// Check-in for a specific schedule by id or uuid, defaulting to current schedule if any.
     CheckIn(CheckIn),
+    /// Estimate the offset of the server clock to the local clock.
+    Calibrate(Calibrate),
+    /// Keep running and check in automatically for every class of the day.
+    Watch(Watch),
+    /// Export schedule of a day, a week or a semester to a calendar file.
+    Export(Export),
+    /// Report per-course attendance from the schedule and recorded check-ins.
+    Report(Report),
+    /// Show or change settings of the profile in the config file.
+    Config(Config),
+    /// Manage the sessions of multiple accounts, keyed by student number.
+    Accounts(Accounts),
 }
 
 /// Login to iClass and save session to a file.
@@ -39,21 +111,45 @@ pub struct Login {
     /// the username
     #[argh(positional)]
     pub username: String,
-    /// the password
+    /// the password, exposed in shell history and process lists; prompted for on the terminal if omitted
     #[argh(positional)]
-    pub password: String,
-    /// the session file path to save to, defaulting to "session.json"
-    #[argh(option, short = 's', default = "String::from(\"session.json\")")]
-    pub session_file: String,
+    pub password: Option<String>,
+    /// read the password from the first line of stdin
+    #[argh(switch)]
+    pub password_stdin: bool,
+    /// read the password from the first line of the output of given shell command, like "pass show iclass"
+    #[argh(option)]
+    pub password_command: Option<String>,
+    /// the session file path to save to, defaulting to the profile's, or "session.json"
+    #[argh(option, short = 's')]
+    pub session_file: Option<String>,
+}
+
+/// List semesters, marking the current one.
+#[derive(Clone, Debug, FromArgs)]
+#[argh(subcommand, name = "semester")]
+pub struct Semester {
+    /// the session file path to load from, defaulting to the profile's, or that of the default stored account, or "session.json"
+    #[argh(option, short = 's')]
+    pub session_file: Option<String>,
+    /// the student number of the stored account to use, instead of the session file
+    #[argh(option)]
+    pub account: Option<String>,
 }
 
 /// List courses in current semester.
 #[derive(Clone, Debug, FromArgs)]
 #[argh(subcommand, name = "courses")]
 pub struct Courses {
-    /// the session file path to load from, defaulting to "session.json"
-    #[argh(option, short = 's', default = "String::from(\"session.json\")")]
-    pub session_file: String,
+    /// the semester code to list courses of, defaulting to current semester
+    #[argh(option)]
+    pub semester: Option<String>,
+    /// the session file path to load from, defaulting to the profile's, or that of the default stored account, or "session.json"
+    #[argh(option, short = 's')]
+    pub session_file: Option<String>,
+    /// the student number of the stored account to use, instead of the session file
+    #[argh(option)]
+    pub account: Option<String>,
 }
 
 /// Get schedule for a specific date or week, defaulting to today.
@@ -61,29 +157,171 @@ pub struct Courses {
 #[argh(subcommand, name = "schedule")]
 pub struct Schedule {
     /// the date to get schedule for in "YYYY-MM-DD" format, defaulting to today
-    #[argh(option, short = 'd', default = "get_today()")]
-    pub date: NaiveDate,
+    #[argh(option, short = 'd')]
+    pub date: Option<NaiveDate>,
     /// show weekly schedule instead of daily schedule
     #[argh(switch, short = 'w')]
     pub weekly: bool,
-    /// the session file path to load from, defaulting to "session.json"
-    #[argh(option, short = 's', default = "String::from(\"session.json\")")]
-    pub session_file: String,
+    /// show weekly schedule of given teaching week in the semester, instead of the week containing the date
+    #[argh(option)]
+    pub week: Option<u32>,
+    /// the semester code to show the whole schedule of, or to count --week in, defaulting to current semester
+    #[argh(option)]
+    pub semester: Option<String>,
+    /// the session file path to load from, defaulting to the profile's, or that of the default stored account, or "session.json"
+    #[argh(option, short = 's')]
+    pub session_file: Option<String>,
+    /// the student number of the stored account to use, instead of the session file
+    #[argh(option)]
+    pub account: Option<String>,
+    /// run for every stored account in turn
+    #[argh(switch)]
+    pub all_accounts: bool,
 }
 
 /// Check-in for a specific schedule by id or uuid, defaulting to current schedule if any.
 #[derive(Clone, Debug, FromArgs)]
 #[argh(subcommand, name = "checkin")]
+#[allow(clippy::struct_excessive_bools, reason = "command line switches")]
 pub struct CheckIn {
-    /// the schedule id or uuid, defaulting to current schedule if any
+    /// the schedule id or uuid, or the URL from a scanned QR code, defaulting to current schedule if any
     #[argh(positional)]
     pub id_or_uuid: Option<String>,
-    /// timestamp or offset (prefixed with '+' or '-') to current time in milliseconds, defaulting to +0 (current time)
+    /// a PNG or JPEG image of the QR code to decode the schedule from, like a screenshot or a photo, instead of giving the schedule
+    #[argh(option, short = 'i')]
+    pub qr_image: Option<String>,
+    /// timestamp or offset (prefixed with '+' or '-') to current time in milliseconds, defaulting to the timestamp in the QR code if any, or +0 (current time)
     #[argh(option, short = 't')]
     pub timestamp_or_offset: Option<String>,
-    /// the session file path to load from, defaulting to "session.json"
-    #[argh(option, short = 's', default = "String::from(\"session.json\")")]
-    pub session_file: String,
+    /// estimate the server clock offset before checking in, and correct the timestamp with it
+    #[argh(switch, short = 'A')]
+    pub auto_offset: bool,
+    /// the clock offset file path to save the estimated offset to with --auto-offset, or to load a saved offset from otherwise
+    #[argh(option, short = 'o')]
+    pub offset_file: Option<String>,
+    /// when picking the current schedule, minutes before a class begins that it becomes eligible, defaulting to 30
+    #[argh(option, short = 'b')]
+    pub before: Option<u32>,
+    /// when picking the current schedule, minutes after a class begins that it stays eligible, defaulting to until the class ends
+    #[argh(option)]
+    pub after: Option<u32>,
+    /// when picking the current schedule, consider classes already checked in as well
+    #[argh(switch)]
+    pub include_checked_in: bool,
+    /// the file path to record check-in attempts to, defaulting to the profile's, or "history.jsonl" next to the config file
+    #[argh(option)]
+    pub history: Option<String>,
+    /// do not record check-in attempts
+    #[argh(switch)]
+    pub no_history: bool,
+    /// the session file path to load from, defaulting to the profile's, or that of the default stored account, or "session.json"
+    #[argh(option, short = 's')]
+    pub session_file: Option<String>,
+    /// the student number of the stored account to use, instead of the session file
+    #[argh(option)]
+    pub account: Option<String>,
+    /// check in for every stored account in turn
+    #[argh(switch)]
+    pub all_accounts: bool,
+}
+
+/// Estimate the offset of the server clock to the local clock.
+#[derive(Clone, Debug, FromArgs)]
+#[argh(subcommand, name = "calibrate")]
+pub struct Calibrate {
+    /// number of requests to sample, defaulting to 8
+    #[argh(option, short = 'n', default = "8")]
+    pub samples: usize,
+    /// the clock offset file path to save the estimated offset to, if any
+    #[argh(option, short = 'o')]
+    pub offset_file: Option<String>,
+}
+
+/// Keep running and check in automatically for every class of the day.
+#[derive(Clone, Debug, FromArgs)]
+#[argh(subcommand, name = "watch")]
+pub struct Watch {
+    /// minutes before a class begins to open the check-in window, defaulting to 30
+    #[argh(option, short = 'b')]
+    pub before: Option<u32>,
+    /// minutes after a class begins to close the check-in window, defaulting to when the class ends
+    #[argh(option)]
+    pub after: Option<u32>,
+    /// maximum random delay in seconds added to the opening of each window, defaulting to 0
+    #[argh(option, short = 'j', default = "0")]
+    pub jitter: u32,
+    /// minutes between schedule refreshes, defaulting to 60; the schedule is also refreshed after midnight
+    #[argh(option, short = 'r', default = "60")]
+    pub refresh: u32,
+    /// the clock offset file path to load a saved offset from, if any
+    #[argh(option, short = 'o')]
+    pub offset_file: Option<String>,
+    /// the file path to append the status log to, in addition to stdout
+    #[argh(option, short = 'l')]
+    pub log_file: Option<String>,
+    /// the file path to record check-in attempts to, defaulting to the profile's, or "history.jsonl" next to the config file
+    #[argh(option)]
+    pub history: Option<String>,
+    /// do not record check-in attempts
+    #[argh(switch)]
+    pub no_history: bool,
+    /// the session file path to load from, defaulting to the profile's, or that of the default stored account, or "session.json"
+    #[argh(option, short = 's')]
+    pub session_file: Option<String>,
+    /// the student number of the stored account to use, instead of the session file
+    #[argh(option)]
+    pub account: Option<String>,
+}
+
+/// Export schedule of a day, a week or a semester to a calendar file.
+#[derive(Clone, Debug, FromArgs)]
+#[argh(subcommand, name = "export")]
+pub struct Export {
+    /// export in iCalendar (.ics) format, currently the only supported format
+    #[argh(switch)]
+    pub ics: bool,
+    /// the date to export schedule for in "YYYY-MM-DD" format, defaulting to today
+    #[argh(option, short = 'd')]
+    pub date: Option<NaiveDate>,
+    /// export the week containing the date
+    #[argh(switch, short = 'w')]
+    pub weekly: bool,
+    /// export the semester containing the date
+    #[argh(switch, short = 'S')]
+    pub semester: bool,
+    /// the file path to write to, defaulting to stdout
+    #[argh(option, short = 'O')]
+    pub output: Option<String>,
+    /// the session file path to load from, defaulting to the profile's, or that of the default stored account, or "session.json"
+    #[argh(option, short = 's')]
+    pub session_file: Option<String>,
+    /// the student number of the stored account to use, instead of the session file
+    #[argh(option)]
+    pub account: Option<String>,
+}
+
+/// Report per-course attendance from the schedule and recorded check-ins.
+#[derive(Clone, Debug, FromArgs)]
+#[argh(subcommand, name = "report")]
+pub struct Report {
+    /// the first date to report in "YYYY-MM-DD" format, defaulting to the beginning of the semester
+    #[argh(option, short = 'f')]
+    pub from: Option<NaiveDate>,
+    /// the last date to report in "YYYY-MM-DD" format, defaulting to today or the end of the semester, whichever is earlier
+    #[argh(option, short = 't')]
+    pub to: Option<NaiveDate>,
+    /// the semester code to report, defaulting to the current semester
+    #[argh(option)]
+    pub semester: Option<String>,
+    /// the file path of recorded check-in attempts, defaulting to the profile's, or "history.jsonl" next to the config file
+    #[argh(option)]
+    pub history: Option<String>,
+    /// the session file path to load from, defaulting to the profile's, or that of the default stored account, or "session.json"
+    #[argh(option, short = 's')]
+    pub session_file: Option<String>,
+    /// the student number of the stored account to use, instead of the session file
+    #[argh(option)]
+    pub account: Option<String>,
 }
 
 /// Timestamp or offset.
@@ -116,12 +354,12 @@ impl FromStr for TimestampOrOffset {
 }
 
 impl TimestampOrOffset {
-    /// Resolve to a timestamp in milliseconds.
-    pub fn resolve(&self) -> u128 {
+    /// Resolve to a timestamp in milliseconds, with offsets relative to `now`.
+    pub const fn resolve(&self, now: u128) -> u128 {
         match self {
             Self::Timestamp(ts) => *ts,
-            Self::Plus(offset) => current_timestamp_millis() + offset,
-            Self::Minus(offset) => current_timestamp_millis().saturating_sub(*offset),
+            Self::Plus(offset) => now + *offset,
+            Self::Minus(offset) => now.saturating_sub(*offset),
         }
     }
 }
@@ -131,3 +369,114 @@ impl Default for TimestampOrOffset {
         Self::Plus(0)
     }
 }
+
+/// Show or change settings of the profile in the config file.
+#[derive(Clone, Debug, FromArgs)]
+#[argh(subcommand, name = "config")]
+pub struct Config {
+    /// the action to take
+    #[argh(subcommand)]
+    pub action: ConfigAction,
+}
+
+/// The available config actions.
+#[derive(Clone, Debug, FromArgs)]
+#[argh(subcommand)]
+pub enum ConfigAction {
+    /// Print a setting of the profile.
+    Get(ConfigGet),
+    /// Change a setting of the profile, or remove it.
+    Set(ConfigSet),
+    /// List settings of the profile.
+    List(ConfigList),
+}
+
+/// Print a setting of the profile.
+#[derive(Clone, Debug, FromArgs)]
+#[argh(subcommand, name = "get")]
+pub struct ConfigGet {
+    /// the key: api-root, session-file, accounts-dir, timestamp-offset, format, proxy, before, after or include-checked-in
+    #[argh(positional)]
+    pub key: Key,
+}
+
+/// Change a setting of the profile, or remove it if no value is given.
+#[derive(Clone, Debug, FromArgs)]
+#[argh(subcommand, name = "set")]
+pub struct ConfigSet {
+    /// the key: api-root, session-file, accounts-dir, timestamp-offset, format, proxy, before, after or include-checked-in
+    #[argh(positional)]
+    pub key: Key,
+    /// the value, removing the key if omitted
+    #[argh(positional)]
+    pub value: Option<String>,
+}
+
+/// List settings of the profile.
+#[derive(Clone, Debug, FromArgs)]
+#[argh(subcommand, name = "list")]
+pub struct ConfigList {}
+
+/// Manage the sessions of multiple accounts, keyed by student number.
+#[derive(Clone, Debug, FromArgs)]
+#[argh(subcommand, name = "accounts")]
+pub struct Accounts {
+    /// the action to take
+    #[argh(subcommand)]
+    pub action: AccountsAction,
+}
+
+/// The available account actions.
+#[derive(Clone, Debug, FromArgs)]
+#[argh(subcommand)]
+pub enum AccountsAction {
+    /// List stored accounts, marking the default one.
+    List(AccountsList),
+    /// Login to iClass and store the session of the account.
+    Add(AccountsAdd),
+    /// Remove a stored account.
+    Remove(AccountsRemove),
+    /// Make a stored account the default.
+    Default(AccountsDefault),
+}
+
+/// List stored accounts, marking the default one.
+#[derive(Clone, Debug, FromArgs)]
+#[argh(subcommand, name = "list")]
+pub struct AccountsList {}
+
+/// Login to iClass and store the session of the account, making it the default if there is none.
+#[derive(Clone, Debug, FromArgs)]
+#[argh(subcommand, name = "add")]
+pub struct AccountsAdd {
+    /// the username
+    #[argh(positional)]
+    pub username: String,
+    /// the password, exposed in shell history and process lists; prompted for on the terminal if omitted
+    #[argh(positional)]
+    pub password: Option<String>,
+    /// read the password from the first line of stdin
+    #[argh(switch)]
+    pub password_stdin: bool,
+    /// read the password from the first line of the output of given shell command, like "pass show iclass"
+    #[argh(option)]
+    pub password_command: Option<String>,
+}
+
+/// Remove a stored account.
+#[derive(Clone, Debug, FromArgs)]
+#[argh(subcommand, name = "remove")]
+pub struct AccountsRemove {
+    /// the student number of the account
+    #[argh(positional)]
+    pub student_no: String,
+}
+
+/// Make a stored account the default, used when no session file is set.
+#[derive(Clone, Debug, FromArgs)]
+#[argh(subcommand, name = "default")]
+pub struct AccountsDefault {
+    /// the student number of the account
+    #[argh(positional)]
+    pub student_no: String,
+}
@@ -0,0 +1,349 @@
+//! Sources of current time, and server clock skew estimation.
+
+use super::{
+    IClass, IClassError,
+    transport::{Transport, TransportRequest},
+    util::CST_TIMEZONE,
+};
+use chrono::{DateTime, FixedOffset, NaiveDate, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    fs::File,
+    io::{BufReader, BufWriter, Error as IoError, Write},
+    path::Path,
+    time::Duration,
+};
+
+/// Time span in milliseconds over which samples are spread, to cover different sub-second phases of the server clock.
+const SAMPLE_SPAN_MILLIS: u64 = 1000;
+
+/// Source of current time, so that time-dependent logic can be run at any given time. See [`IClass::set_clock`].
+pub trait Clock: Send + Sync {
+    /// Gets current time.
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Gets current timestamp in milliseconds since UNIX epoch, or 0 if before it.
+    fn timestamp_millis(&self) -> u128 {
+        self.now().timestamp_millis().try_into().unwrap_or_default()
+    }
+
+    /// Gets today in China Standard Time.
+    fn today(&self) -> NaiveDate {
+        self.now().with_timezone(&CST_TIMEZONE).date_naive()
+    }
+}
+
+/// The system clock, used by default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+/// A clock stopped at given time.
+#[derive(Clone, Copy, Debug)]
+pub struct FixedClock(pub DateTime<Utc>);
+
+/// A clock running given offset ahead of (or behind, if negative) another clock.
+#[derive(Clone, Copy, Debug)]
+pub struct OffsetClock<C = SystemClock> {
+    /// The underlying clock.
+    pub inner: C,
+    /// Offset added to the underlying clock.
+    pub offset: TimeDelta,
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0
+    }
+}
+
+impl<C: Clock> Clock for OffsetClock<C> {
+    fn now(&self) -> DateTime<Utc> {
+        self.inner.now() + self.offset
+    }
+}
+
+impl<C: Clock> OffsetClock<C> {
+    /// Creates a clock running given offset ahead of given clock.
+    pub const fn new(inner: C, offset: TimeDelta) -> Self {
+        Self { inner, offset }
+    }
+
+    /// Creates a clock that reads given time now, and runs along with given clock from then on.
+    pub fn starting_at(inner: C, start: DateTime<Utc>) -> Self {
+        let offset = start - inner.now();
+        Self { inner, offset }
+    }
+}
+
+/// Estimated offset of the server clock to the local clock.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClockOffset {
+    /// Offset in milliseconds, positive if the server clock is ahead of the local clock.
+    pub offset_millis: i64,
+    /// Uncertainty of the offset in milliseconds, i.e. the true offset is within `offset_millis ± uncertainty_millis`.
+    pub uncertainty_millis: u64,
+    /// Local timestamp in milliseconds when the offset was measured.
+    pub measured_at: u128,
+}
+
+/// A single measurement: local send time, local receive time and server time (seconds precision), all in milliseconds.
+struct Sample {
+    sent: i128,
+    received: i128,
+    server: i128,
+}
+
+#[allow(
+    clippy::future_not_send,
+    reason = "`Send` whenever the transport is, see `Transport`"
+)]
+impl<T: Transport> IClass<T> {
+    /// Estimates the offset of the server clock to the local clock, using the `Date` header and round-trip time of `samples` requests to the API root.
+    ///
+    /// Since the `Date` header only has a precision of one second, each sample bounds the offset to an interval. Samples are spread over a second and their intervals are intersected, so more samples give a tighter estimate.
+    ///
+    /// Round trips are timed by the [`SystemClock`] rather than the injected [`clock`](Self::clock), since the server clock runs in real time, and a stopped clock would measure no round trip at all.
+    ///
+    /// # Errors
+    ///
+    /// See [`IClassError`]. [`IClassError::DataParsingError`] if the server does not send a valid `Date` header.
+    pub async fn estimate_clock_offset(&self, samples: usize) -> Result<ClockOffset, IClassError> {
+        let samples = samples.max(1);
+        let interval = Duration::from_millis(SAMPLE_SPAN_MILLIS / samples as u64 + 1);
+        let mut measured = Vec::with_capacity(samples);
+        for i in 0..samples {
+            if i > 0 {
+                self.transport.sleep(interval).await;
+            }
+            let sent = SystemClock.timestamp_millis();
+            let request = TransportRequest::get(self.api_root.clone());
+            let response = self.transport.send(request).await?;
+            let received = SystemClock.timestamp_millis();
+            let server = response
+                .headers
+                .get("Date")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| DateTime::parse_from_rfc2822(value).ok())
+                .ok_or(IClassError::DataParsingError)?
+                .timestamp_millis();
+            measured.push(Sample {
+                sent: sent.try_into().unwrap_or(i128::MAX),
+                received: received.try_into().unwrap_or(i128::MAX),
+                server: server.into(),
+            });
+        }
+        Ok(ClockOffset::from_samples(&measured))
+    }
+
+    /// Estimates the offset of the server clock like [`estimate_clock_offset`](Self::estimate_clock_offset), and applies it to [`server_timestamp_millis`](Self::server_timestamp_millis).
+    ///
+    /// # Errors
+    ///
+    /// See [`IClassError`].
+    pub async fn calibrate_clock(&mut self, samples: usize) -> Result<ClockOffset, IClassError> {
+        let offset = self.estimate_clock_offset(samples).await?;
+        self.clock_offset = offset.offset_millis;
+        Ok(offset)
+    }
+
+    /// Gets the offset of the server clock to the local clock in milliseconds.
+    #[must_use]
+    pub const fn clock_offset(&self) -> i64 {
+        self.clock_offset
+    }
+
+    /// Sets the offset of the server clock to the local clock in milliseconds, for example from a saved [`ClockOffset`].
+    pub const fn set_clock_offset(&mut self, offset_millis: i64) {
+        self.clock_offset = offset_millis;
+    }
+
+    /// Gets the source of current time.
+    #[must_use]
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_deref().unwrap_or(&SystemClock)
+    }
+
+    /// Sets the source of current time, replacing the [`SystemClock`]. The server clock offset still applies on top of it.
+    pub fn set_clock<C: Clock + 'static>(&mut self, clock: C) {
+        self.clock = Some(Box::new(clock));
+    }
+
+    /// Gets current timestamp in milliseconds as seen by the server, i.e. local time from the [`clock`](Self::clock) corrected by the clock offset. Use this for check-in timestamps.
+    #[must_use]
+    pub fn server_timestamp_millis(&self) -> u128 {
+        ClockOffset::apply_millis(self.clock_offset, self.clock().timestamp_millis())
+    }
+
+    /// Gets current time as seen by the server, in China Standard Time.
+    #[must_use]
+    pub fn server_now(&self) -> DateTime<FixedOffset> {
+        let millis = i64::try_from(self.server_timestamp_millis()).unwrap_or(i64::MAX);
+        DateTime::from_timestamp_millis(millis)
+            .unwrap_or_default()
+            .with_timezone(&CST_TIMEZONE)
+    }
+}
+
+impl ClockOffset {
+    /// Estimates the offset from given samples.
+    fn from_samples(samples: &[Sample]) -> Self {
+        // The server time when responding is within [server, server + 1000), and the response is generated within [sent, received] locally
+        let lower = samples
+            .iter()
+            .map(|s| s.server - s.received)
+            .max()
+            .unwrap_or_default();
+        let upper = samples
+            .iter()
+            .map(|s| s.server + 1000 - s.sent)
+            .min()
+            .unwrap_or_default();
+        let (offset, uncertainty) = if lower <= upper {
+            (i128::midpoint(lower, upper), (upper - lower) / 2)
+        } else {
+            // Inconsistent samples, possibly due to a jumping clock; fall back to the median of midpoint estimates
+            let mut estimates: Vec<_> = samples
+                .iter()
+                .map(|s| s.server + 500 - i128::midpoint(s.sent, s.received))
+                .collect();
+            estimates.sort_unstable();
+            let spread = estimates.last().copied().unwrap_or_default()
+                - estimates.first().copied().unwrap_or_default();
+            (estimates[estimates.len() / 2], spread / 2 + 500)
+        };
+        Self {
+            offset_millis: offset.try_into().unwrap_or_default(),
+            uncertainty_millis: uncertainty.try_into().unwrap_or_default(),
+            measured_at: samples
+                .last()
+                .map_or(0, |s| s.received.try_into().unwrap_or_default()),
+        }
+    }
+
+    /// Applies the offset to given local timestamp in milliseconds.
+    #[must_use]
+    pub const fn apply(&self, timestamp_millis: u128) -> u128 {
+        Self::apply_millis(self.offset_millis, timestamp_millis)
+    }
+
+    /// Applies given offset to given local timestamp in milliseconds.
+    const fn apply_millis(offset_millis: i64, timestamp_millis: u128) -> u128 {
+        let magnitude = offset_millis.unsigned_abs() as u128;
+        if offset_millis >= 0 {
+            timestamp_millis.saturating_add(magnitude)
+        } else {
+            timestamp_millis.saturating_sub(magnitude)
+        }
+    }
+
+    /// Saves the offset to a file.
+    ///
+    /// # Errors
+    ///
+    /// [IO errors](IoError) during file operations.
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), IoError> {
+        let file = File::create(path)?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Loads the offset from a file.
+    ///
+    /// # Errors
+    ///
+    /// [IO errors](IoError) during file operations.
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, IoError> {
+        let file = File::open(path)?;
+        let reader = BufReader::new(file);
+        let offset = serde_json::from_reader(reader)?;
+        Ok(offset)
+    }
+}
+
+impl fmt::Display for ClockOffset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            offset_millis,
+            uncertainty_millis,
+            ..
+        } = self;
+        write!(f, "{offset_millis:+} ms (± {uncertainty_millis} ms)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Clock, ClockOffset, FixedClock, OffsetClock, Sample};
+    use chrono::{DateTime, TimeDelta};
+
+    /// A sample of a server running `offset` ahead, responding `reply` ms after the request was sent, with a round trip of `rtt` ms.
+    fn sample(sent: i128, rtt: i128, reply: i128, offset: i128) -> Sample {
+        let server = sent + reply + offset;
+        Sample {
+            sent,
+            received: sent + rtt,
+            // The `Date` header truncates to seconds
+            server: server - server.rem_euclid(1000),
+        }
+    }
+
+    #[test]
+    fn samples_narrow_the_offset() {
+        let offset = 60_250;
+        let one = ClockOffset::from_samples(&[sample(1_000_000, 40, 20, offset)]);
+        assert!((one.offset_millis - 60_250).unsigned_abs() <= one.uncertainty_millis);
+        assert_eq!(one.measured_at, 1_000_040);
+
+        // Spread over a second, the samples catch the second boundary of the server clock
+        let samples: Vec<_> = (0..8)
+            .map(|i| sample(1_000_000 + i * 126, 40, 20, offset))
+            .collect();
+        let many = ClockOffset::from_samples(&samples);
+        assert!((many.offset_millis - 60_250).unsigned_abs() <= many.uncertainty_millis);
+        assert!(many.uncertainty_millis < one.uncertainty_millis);
+        assert!(many.uncertainty_millis <= 100);
+    }
+
+    #[test]
+    fn inconsistent_samples_fall_back_to_median() {
+        // The local clock jumps ahead by a minute between samples
+        let samples = [
+            sample(1_000_000, 40, 20, 5_000),
+            sample(1_000_500, 40, 20, 5_000),
+            sample(1_061_000, 40, 20, -55_000),
+        ];
+        let offset = ClockOffset::from_samples(&samples);
+        assert!((offset.offset_millis - 5_000).abs() <= 500, "{offset}");
+        assert!(offset.uncertainty_millis >= 30_000);
+    }
+
+    #[test]
+    fn apply_offsets() {
+        assert_eq!(ClockOffset::apply_millis(1_500, 10_000), 11_500);
+        assert_eq!(ClockOffset::apply_millis(-1_500, 10_000), 8_500);
+        assert_eq!(ClockOffset::apply_millis(-20_000, 10_000), 0);
+    }
+
+    #[test]
+    fn fixed_and_offset_clocks() {
+        let start = DateTime::from_timestamp_millis(1_760_000_000_000).unwrap();
+        let fixed = FixedClock(start);
+        assert_eq!(fixed.timestamp_millis(), 1_760_000_000_000);
+        assert_eq!(fixed.today().to_string(), "2025-10-09");
+
+        let ahead = OffsetClock::new(fixed, TimeDelta::hours(12));
+        assert_eq!(ahead.today().to_string(), "2025-10-10");
+        let restarted = OffsetClock::starting_at(ahead, start);
+        assert_eq!(restarted.now(), start);
+    }
+}
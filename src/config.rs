@@ -0,0 +1,637 @@
+//! Configuration file with named profiles, stored as TOML in the XDG config directory.
+
+use super::{
+    accounts::{AccountStore, DEFAULT_ACCOUNTS_DIR},
+    cli::TimestampOrOffset,
+    output::Format,
+};
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    env, fmt, fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use ucas_iclass::CheckInPolicy;
+use url::Url;
+
+/// Environment variable for the profile to use.
+pub const PROFILE_ENV: &str = "UCAS_ICLASS_PROFILE";
+/// Profile used when none is chosen.
+pub const DEFAULT_PROFILE: &str = "default";
+/// Session file used when none is set.
+pub const DEFAULT_SESSION_FILE: &str = "session.json";
+/// Name of the check-in history file used when none is set, placed next to the configuration file.
+pub const DEFAULT_HISTORY_FILE: &str = "history.jsonl";
+/// Generic proxy environment variables for HTTPS requests, which take precedence over the proxy in the profile.
+pub const SYSTEM_PROXY_ENV: [&str; 4] = ["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"];
+
+/// A setting stored in a profile, also settable by an environment variable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Key {
+    /// API root URL.
+    ApiRoot,
+    /// Session file path.
+    SessionFile,
+    /// Directory of the account store.
+    AccountsDir,
+    /// Check-in history file path.
+    History,
+    /// Default timestamp offset for check-in.
+    TimestampOffset,
+    /// Output format.
+    Format,
+    /// Proxy URL.
+    Proxy,
+    /// Minutes before a class begins that it becomes eligible for check-in.
+    Before,
+    /// Minutes after a class begins that it stays eligible for check-in.
+    After,
+    /// Whether classes already checked in are eligible for check-in.
+    IncludeCheckedIn,
+}
+
+/// A named set of settings.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Profile {
+    /// API root URL.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    api_root: Option<String>,
+    /// Session file path.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session_file: Option<String>,
+    /// Directory of the account store.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    accounts_dir: Option<String>,
+    /// Check-in history file path.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    history: Option<String>,
+    /// Default timestamp offset for check-in, like `+30000`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timestamp_offset: Option<String>,
+    /// Output format.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<String>,
+    /// Proxy URL.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    proxy: Option<String>,
+    /// Minutes before a class begins that it becomes eligible for check-in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    before: Option<u32>,
+    /// Minutes after a class begins that it stays eligible for check-in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    after: Option<u32>,
+    /// Whether classes already checked in are eligible for check-in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    include_checked_in: Option<bool>,
+}
+
+/// Contents of the configuration file.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Profiles by name.
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// Settings of the chosen profile, resolved against flags and environment variables.
+pub struct Settings {
+    /// Path of the configuration file, if the config directory can be located.
+    pub path: Option<PathBuf>,
+    /// Contents of the configuration file.
+    pub config: Config,
+    /// Name of the chosen profile.
+    pub name: String,
+    /// Whether the profile is chosen explicitly, by flag or environment variable.
+    pub explicit: bool,
+    /// Looks up an environment variable.
+    pub env: fn(&str) -> Option<String>,
+}
+
+impl Key {
+    /// All keys, in the order they are listed.
+    pub const ALL: [Self; 10] = [
+        Self::ApiRoot,
+        Self::SessionFile,
+        Self::AccountsDir,
+        Self::History,
+        Self::TimestampOffset,
+        Self::Format,
+        Self::Proxy,
+        Self::Before,
+        Self::After,
+        Self::IncludeCheckedIn,
+    ];
+
+    /// Name of the key in the configuration file.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::ApiRoot => "api-root",
+            Self::SessionFile => "session-file",
+            Self::AccountsDir => "accounts-dir",
+            Self::History => "history",
+            Self::TimestampOffset => "timestamp-offset",
+            Self::Format => "format",
+            Self::Proxy => "proxy",
+            Self::Before => "before",
+            Self::After => "after",
+            Self::IncludeCheckedIn => "include-checked-in",
+        }
+    }
+
+    /// Environment variable overriding the key, like `UCAS_ICLASS_API_ROOT`.
+    pub fn env(self) -> String {
+        format!(
+            "UCAS_ICLASS_{}",
+            self.name().to_uppercase().replace('-', "_")
+        )
+    }
+}
+
+impl FromStr for Key {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|key| key.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = Self::ALL.iter().map(|key| key.name()).collect();
+                format!("unknown key: {s}, expected one of {}", names.join(", "))
+            })
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Profile {
+    /// Gets the value of given key, if set.
+    pub fn get(&self, key: Key) -> Option<String> {
+        match key {
+            Key::ApiRoot => self.api_root.clone(),
+            Key::SessionFile => self.session_file.clone(),
+            Key::AccountsDir => self.accounts_dir.clone(),
+            Key::History => self.history.clone(),
+            Key::TimestampOffset => self.timestamp_offset.clone(),
+            Key::Format => self.format.clone(),
+            Key::Proxy => self.proxy.clone(),
+            Key::Before => self.before.map(|before| before.to_string()),
+            Key::After => self.after.map(|after| after.to_string()),
+            Key::IncludeCheckedIn => self.include_checked_in.map(|include| include.to_string()),
+        }
+    }
+
+    /// Sets the value of given key after checking it, or removes it if `None`.
+    pub fn set(&mut self, key: Key, value: Option<&str>) -> Result<()> {
+        let invalid = |e: String| anyhow!("Invalid value for {key}: {e}");
+        match key {
+            Key::ApiRoot => self.api_root = checked::<Url>(value).map_err(invalid)?,
+            Key::SessionFile => self.session_file = value.map(str::to_string),
+            Key::AccountsDir => self.accounts_dir = value.map(str::to_string),
+            Key::History => self.history = value.map(str::to_string),
+            Key::TimestampOffset => {
+                value.map(parse_offset).transpose().map_err(invalid)?;
+                self.timestamp_offset = value.map(str::to_string);
+            }
+            Key::Format => self.format = checked::<Format>(value).map_err(invalid)?,
+            Key::Proxy => self.proxy = checked::<Url>(value).map_err(invalid)?,
+            Key::Before => self.before = parsed(value).map_err(invalid)?,
+            Key::After => self.after = parsed(value).map_err(invalid)?,
+            Key::IncludeCheckedIn => self.include_checked_in = parsed(value).map_err(invalid)?,
+        }
+        Ok(())
+    }
+}
+
+impl Config {
+    /// Path of the configuration file, `ucas-iclass/config.toml` under `XDG_CONFIG_HOME`, defaulting to `~/.config`. `APPDATA` is used if neither is available.
+    pub fn path() -> Option<PathBuf> {
+        let absolute = |var: &str| {
+            env::var_os(var)
+                .map(PathBuf::from)
+                .filter(|path| path.is_absolute())
+        };
+        absolute("XDG_CONFIG_HOME")
+            .or_else(|| absolute("HOME").map(|home| home.join(".config")))
+            .or_else(|| absolute("APPDATA"))
+            .map(|dir| dir.join("ucas-iclass").join("config.toml"))
+    }
+
+    /// Loads the configuration from given file, empty if it does not exist.
+    pub fn load(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(content) => toml::from_str(&content)
+                .with_context(|| format!("Invalid config file {}", path.display())),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Saves the configuration to given file, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+impl Settings {
+    /// Loads the configuration file, choosing the profile by flag, the [`PROFILE_ENV`] environment variable, or [`DEFAULT_PROFILE`] in order.
+    pub fn load(profile: Option<String>) -> Result<Self> {
+        let path = Config::path();
+        let config = path
+            .as_deref()
+            .map(Config::load)
+            .transpose()?
+            .unwrap_or_default();
+        Ok(Self::new(path, config, profile, |name| env::var(name).ok()))
+    }
+
+    /// Settings of given configuration, choosing the profile like [`Self::load`] with environment variables looked up by `env`.
+    pub fn new(
+        path: Option<PathBuf>,
+        config: Config,
+        profile: Option<String>,
+        env: fn(&str) -> Option<String>,
+    ) -> Self {
+        let profile = profile.or_else(|| env(PROFILE_ENV));
+        Self {
+            path,
+            config,
+            explicit: profile.is_some(),
+            name: profile.unwrap_or_else(|| DEFAULT_PROFILE.to_string()),
+            env,
+        }
+    }
+
+    /// The chosen profile, if present in the configuration file.
+    pub fn profile(&self) -> Option<&Profile> {
+        self.config.profiles.get(&self.name)
+    }
+
+    /// Resolves a setting: the flag if given, or else the environment variable of the key if set, or else the value in the profile if any.
+    pub fn get<T>(&self, key: Key, flag: Option<T>) -> Result<Option<T>>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        if flag.is_some() {
+            return Ok(flag);
+        }
+        let env = key.env();
+        if let Some(value) = (self.env)(&env) {
+            return value
+                .parse()
+                .map(Some)
+                .map_err(|e| anyhow!("Invalid {env}: {e}"));
+        }
+        self.profile()
+            .and_then(|profile| profile.get(key))
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|e| anyhow!("Invalid {key} in profile {}: {e}", self.name))
+            })
+            .transpose()
+    }
+
+    /// Resolves the session file path to log in to, defaulting to [`DEFAULT_SESSION_FILE`].
+    pub fn session_file(&self, flag: Option<String>) -> Result<String> {
+        Ok(self
+            .get(Key::SessionFile, flag)?
+            .unwrap_or_else(|| DEFAULT_SESSION_FILE.to_string()))
+    }
+
+    /// Resolves the session file path to load from: that of given stored account, or else the session file setting, or else that of the default stored account if any, or else [`DEFAULT_SESSION_FILE`]. Also returns whether it belongs to a stored account.
+    pub fn load_session_file(
+        &self,
+        flag: Option<String>,
+        account: Option<&str>,
+    ) -> Result<(PathBuf, bool)> {
+        let accounts = self.accounts()?;
+        if let Some(account) = account {
+            if flag.is_some() {
+                bail!("--account and -s cannot be used together");
+            }
+            return Ok((accounts.session_file(account)?, true));
+        }
+        if let Some(session_file) = self.get::<String>(Key::SessionFile, flag)? {
+            return Ok((session_file.into(), false));
+        }
+        match accounts.default_account()? {
+            Some(account) => Ok((accounts.session_file(&account)?, true)),
+            None => Ok((DEFAULT_SESSION_FILE.into(), false)),
+        }
+    }
+
+    /// Opens the account store, in the directory set, defaulting to [`DEFAULT_ACCOUNTS_DIR`] next to the configuration file.
+    pub fn accounts(&self) -> Result<AccountStore> {
+        Ok(AccountStore::new(
+            self.get::<String>(Key::AccountsDir, None)?
+                .map_or_else(|| self.next_to_config(DEFAULT_ACCOUNTS_DIR), PathBuf::from),
+        ))
+    }
+
+    /// Resolves the check-in history file path, defaulting to [`DEFAULT_HISTORY_FILE`] next to the configuration file.
+    pub fn history(&self, flag: Option<String>) -> Result<PathBuf> {
+        Ok(self
+            .get::<String>(Key::History, flag)?
+            .map_or_else(|| self.next_to_config(DEFAULT_HISTORY_FILE), PathBuf::from))
+    }
+
+    /// Path of given file name in the directory of the configuration file, or in the working directory if the config directory cannot be located.
+    fn next_to_config(&self, name: &str) -> PathBuf {
+        self.path
+            .as_deref()
+            .and_then(Path::parent)
+            .map_or_else(|| name.into(), |dir| dir.join(name))
+    }
+
+    /// Resolves the proxy like [`Self::get`], except that the proxy in the profile is ignored if any of [`SYSTEM_PROXY_ENV`] is set.
+    pub fn proxy(&self, flag: Option<Url>) -> Result<Option<Url>> {
+        let set = |name: &str| (self.env)(name).is_some_and(|value| !value.is_empty());
+        if flag.is_none() && !set(&Key::Proxy.env()) && SYSTEM_PROXY_ENV.into_iter().any(set) {
+            return Ok(None);
+        }
+        self.get(Key::Proxy, flag)
+    }
+
+    /// Resolves the default timestamp offset for check-in, `+0` if unset.
+    pub fn timestamp_offset(&self) -> Result<TimestampOrOffset> {
+        self.get::<String>(Key::TimestampOffset, None)?.map_or(
+            Ok(TimestampOrOffset::default()),
+            |offset| {
+                parse_offset(&offset).map_err(|e| anyhow!("Invalid {}: {e}", Key::TimestampOffset))
+            },
+        )
+    }
+
+    /// Resolves the policy of picking the current schedule, with defaults of [`CheckInPolicy`].
+    ///
+    /// Schedules already checked in are skipped, see [`Self::include_checked_in`] to resolve that.
+    pub fn check_in_policy(
+        &self,
+        before: Option<u32>,
+        after: Option<u32>,
+    ) -> Result<CheckInPolicy> {
+        let minutes = |minutes: u32| Duration::minutes(minutes.into());
+        Ok(CheckInPolicy {
+            lead_time: self
+                .get(Key::Before, before)?
+                .map_or_else(|| CheckInPolicy::default().lead_time, minutes),
+            grace_period: self.get(Key::After, after)?.map(minutes),
+            include_checked_in: false,
+        })
+    }
+
+    /// Resolves whether `checkin` picks schedules already checked in, off by default.
+    pub fn include_checked_in(&self, flag: bool) -> Result<bool> {
+        Ok(self
+            .get(Key::IncludeCheckedIn, flag.then_some(true))?
+            .unwrap_or_default())
+    }
+}
+
+/// Parses a timestamp offset, rejecting absolute timestamps.
+fn parse_offset(s: &str) -> Result<TimestampOrOffset, String> {
+    match s.parse() {
+        Ok(offset @ (TimestampOrOffset::Plus(_) | TimestampOrOffset::Minus(_))) => Ok(offset),
+        _ => Err(format!(
+            "expected an offset prefixed with '+' or '-', got {s}"
+        )),
+    }
+}
+
+/// Checks that the value parses, keeping it as a string.
+fn checked<T>(value: Option<&str>) -> Result<Option<String>, String>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    parsed::<T>(value)?;
+    Ok(value.map(str::to_string))
+}
+
+/// Parses the value.
+fn parsed<T>(value: Option<&str>) -> Result<Option<T>, String>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    value
+        .map(|value| value.parse().map_err(|e: T::Err| e.to_string()))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Config, DEFAULT_ACCOUNTS_DIR, DEFAULT_HISTORY_FILE, DEFAULT_PROFILE, Key, Profile, Settings,
+    };
+    use std::path::PathBuf;
+    use url::Url;
+
+    /// Configuration with given profiles.
+    fn config(profiles: &[(&str, Profile)]) -> Config {
+        Config {
+            profiles: profiles
+                .iter()
+                .map(|(name, profile)| ((*name).to_string(), profile.clone()))
+                .collect(),
+        }
+    }
+
+    /// Settings with given profiles, the first one chosen, and no environment variables.
+    fn settings(path: Option<&str>, profiles: &[(&str, Profile)]) -> Settings {
+        Settings::new(
+            path.map(PathBuf::from),
+            config(profiles),
+            Some(profiles[0].0.to_string()),
+            |_| None,
+        )
+    }
+
+    /// A profile with given values.
+    fn profile(values: &[(Key, &str)]) -> Profile {
+        let mut profile = Profile::default();
+        for &(key, value) in values {
+            profile.set(key, Some(value)).unwrap();
+        }
+        profile
+    }
+
+    #[test]
+    fn history_next_to_config_file() {
+        let settings = settings(
+            Some("/home/me/.config/ucas-iclass/config.toml"),
+            &[("default", Profile::default())],
+        );
+        assert_eq!(
+            settings.history(None).unwrap(),
+            PathBuf::from("/home/me/.config/ucas-iclass").join(DEFAULT_HISTORY_FILE)
+        );
+        assert_eq!(
+            settings.history(Some("h.jsonl".to_string())).unwrap(),
+            PathBuf::from("h.jsonl")
+        );
+
+        let settings = Settings {
+            path: None,
+            ..settings
+        };
+        assert_eq!(
+            settings.history(None).unwrap(),
+            PathBuf::from(DEFAULT_HISTORY_FILE)
+        );
+    }
+
+    #[test]
+    fn accounts_next_to_config_file() {
+        let settings = settings(
+            Some("/home/me/.config/ucas-iclass/config.toml"),
+            &[("default", Profile::default())],
+        );
+        assert_eq!(
+            settings.accounts().unwrap().dir(),
+            PathBuf::from("/home/me/.config/ucas-iclass").join(DEFAULT_ACCOUNTS_DIR)
+        );
+
+        let settings = self::settings(
+            Some("/config/config.toml"),
+            &[("default", profile(&[(Key::AccountsDir, "/data/accounts")]))],
+        );
+        assert_eq!(
+            settings.accounts().unwrap().dir(),
+            PathBuf::from("/data/accounts")
+        );
+    }
+
+    #[test]
+    fn history_from_profile() {
+        let mut profile = Profile::default();
+        profile
+            .set(Key::History, Some("/data/history.jsonl"))
+            .unwrap();
+        let settings = settings(Some("/config/config.toml"), &[("default", profile)]);
+        assert_eq!(
+            settings.history(None).unwrap(),
+            PathBuf::from("/data/history.jsonl")
+        );
+    }
+
+    #[test]
+    fn profile_selection() {
+        let profiles = config(&[
+            ("default", profile(&[(Key::Format, "json")])),
+            ("lab", profile(&[(Key::Format, "jsonl")])),
+            ("home", profile(&[(Key::Format, "table")])),
+        ]);
+        let chosen = |flag: Option<&str>, env: fn(&str) -> Option<String>| {
+            let settings = Settings::new(None, profiles.clone(), flag.map(str::to_string), env);
+            (
+                settings.name.clone(),
+                settings.explicit,
+                settings.get::<String>(Key::Format, None).unwrap(),
+            )
+        };
+        let no_env = |_: &str| None;
+        let lab_env = |name: &str| (name == "UCAS_ICLASS_PROFILE").then(|| "lab".to_string());
+
+        assert_eq!(
+            chosen(None, no_env),
+            (DEFAULT_PROFILE.to_string(), false, Some("json".to_string()))
+        );
+        assert_eq!(
+            chosen(None, lab_env),
+            ("lab".to_string(), true, Some("jsonl".to_string()))
+        );
+        assert_eq!(
+            chosen(Some("home"), lab_env),
+            ("home".to_string(), true, Some("table".to_string()))
+        );
+        let (name, explicit, format) = chosen(Some("missing"), no_env);
+        assert_eq!((name.as_str(), explicit, format), ("missing", true, None));
+    }
+
+    #[test]
+    fn flag_over_env_over_profile() {
+        let profiles = config(&[(
+            "default",
+            profile(&[(Key::Before, "10"), (Key::After, "5")]),
+        )]);
+        let env = |name: &str| (name == "UCAS_ICLASS_BEFORE").then(|| "20".to_string());
+        let settings = Settings::new(None, profiles.clone(), None, env);
+        assert_eq!(settings.get(Key::Before, Some(30_u32)).unwrap(), Some(30));
+        assert_eq!(settings.get::<u32>(Key::Before, None).unwrap(), Some(20));
+        assert_eq!(settings.get::<u32>(Key::After, None).unwrap(), Some(5));
+        assert_eq!(
+            settings.get::<u32>(Key::IncludeCheckedIn, None).unwrap(),
+            None
+        );
+
+        let env = |name: &str| (name == "UCAS_ICLASS_BEFORE").then(|| "soon".to_string());
+        let settings = Settings::new(None, profiles, None, env);
+        assert!(settings.get::<u32>(Key::Before, None).is_err());
+        assert_eq!(settings.get(Key::Before, Some(30_u32)).unwrap(), Some(30));
+    }
+
+    #[test]
+    fn profile_proxy_yields_to_system_proxy() {
+        let profiles = config(&[(
+            "default",
+            profile(&[(Key::Proxy, "socks5h://127.0.0.1:1080")]),
+        )]);
+        let url = |url: &str| Some(Url::parse(url).unwrap());
+        let proxy = |flag: Option<&str>, env: fn(&str) -> Option<String>| {
+            Settings::new(None, profiles.clone(), None, env)
+                .proxy(flag.and_then(url))
+                .unwrap()
+        };
+        let system = |name: &str| (name == "HTTPS_PROXY").then(|| "http://system:8080".to_string());
+        let all = |name: &str| (name == "all_proxy").then(|| "http://system:8080".to_string());
+        let empty = |name: &str| (name == "HTTPS_PROXY").then(String::new);
+        let both = |name: &str| match name {
+            "HTTPS_PROXY" => Some("http://system:8080".to_string()),
+            "UCAS_ICLASS_PROXY" => Some("http://own:3128".to_string()),
+            _ => None,
+        };
+
+        assert_eq!(proxy(None, |_| None), url("socks5h://127.0.0.1:1080"));
+        assert_eq!(proxy(None, empty), url("socks5h://127.0.0.1:1080"));
+        assert_eq!(proxy(None, system), None);
+        assert_eq!(proxy(None, all), None);
+        assert_eq!(proxy(None, both), url("http://own:3128"));
+        assert_eq!(
+            proxy(Some("http://flag:3128"), system),
+            url("http://flag:3128")
+        );
+    }
+
+    #[test]
+    fn include_checked_in_only_by_request() {
+        let settings = settings(
+            None,
+            &[("default", profile(&[(Key::IncludeCheckedIn, "true")]))],
+        );
+        assert!(settings.include_checked_in(false).unwrap());
+        assert!(
+            !settings
+                .check_in_policy(None, None)
+                .unwrap()
+                .include_checked_in
+        );
+
+        let settings = self::settings(None, &[("default", Profile::default())]);
+        assert!(!settings.include_checked_in(false).unwrap());
+        assert!(settings.include_checked_in(true).unwrap());
+    }
+}
@@ -0,0 +1,294 @@
+//! Local append-only history of check-in attempts, stored as JSON lines.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write},
+    path::PathBuf,
+};
+use ucas_iclass::{
+    CheckInResult, Course, IClass, IClassError, Schedule,
+    transport::Transport,
+    util::{CST_TIMEZONE, deserialize_str_to_datetime, serialize_datetime_to_str},
+};
+
+/// Version of the entry format.
+const ENTRY_VERSION: u32 = 1;
+
+/// Outcome of a check-in attempt.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Outcome {
+    /// Checked in successfully.
+    Success,
+    /// The server responded, but reported failure.
+    Failed,
+    /// Already checked in before.
+    AlreadyCheckedIn,
+    /// An error occurred.
+    Error,
+}
+
+impl Outcome {
+    /// Whether the class is checked in after this attempt.
+    pub const fn is_checked_in(self) -> bool {
+        matches!(self, Self::Success | Self::AlreadyCheckedIn)
+    }
+}
+
+/// A check-in attempt.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckInEntry {
+    /// Version of the entry format.
+    pub version: u32,
+    /// Local time of the attempt, in China Standard Time.
+    #[serde(
+        serialize_with = "serialize_datetime_to_str",
+        deserialize_with = "deserialize_str_to_datetime"
+    )]
+    pub time: DateTime<FixedOffset>,
+    /// Student number of the account, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub student_no: Option<String>,
+    /// Whether the schedule is identified by `id` or `uuid`.
+    pub by: String,
+    /// The schedule id or uuid.
+    pub schedule: String,
+    /// Id of the course, if known.
+    pub course_id: Option<String>,
+    /// Name of the course, if known.
+    pub course_name: Option<String>,
+    /// The timestamp used in milliseconds.
+    pub timestamp: u128,
+    /// Server clock offset applied, in milliseconds.
+    pub offset_millis: i64,
+    /// Outcome of the attempt.
+    pub outcome: Outcome,
+    /// Id of the check-in record, if checked in.
+    pub sign_id: Option<String>,
+    /// Error message, if any.
+    pub error: Option<String>,
+}
+
+impl CheckInEntry {
+    /// Creates an entry for an attempt to check in the schedule with given id or uuid.
+    pub fn new<T: Transport>(
+        iclass: &IClass<T>,
+        by: &str,
+        schedule: &str,
+        course: Option<&Course>,
+        timestamp: u128,
+        result: &Result<CheckInResult, IClassError>,
+    ) -> Self {
+        let (outcome, sign_id, error) = match result {
+            Ok(result) if result.status => (Outcome::Success, Some(result.id.clone()), None),
+            Ok(result) => (Outcome::Failed, Some(result.id.clone()), None),
+            Err(e @ IClassError::AlreadyCheckedIn(_)) => {
+                (Outcome::AlreadyCheckedIn, None, Some(e.to_string()))
+            }
+            Err(e) => (Outcome::Error, None, Some(e.to_string())),
+        };
+        Self {
+            version: ENTRY_VERSION,
+            time: iclass.clock().now().with_timezone(&CST_TIMEZONE),
+            student_no: iclass.user_session().map(|session| session.student_no),
+            by: by.to_string(),
+            schedule: schedule.to_string(),
+            course_id: course.map(|course| course.course_id.clone()),
+            course_name: course.map(|course| course.course_name.clone()),
+            timestamp,
+            offset_millis: iclass.clock_offset(),
+            outcome,
+            sign_id,
+            error,
+        }
+    }
+
+    /// Whether this entry may belong to the account with given student number, i.e. it is recorded for that account or for an unknown one.
+    pub fn may_be_of(&self, student_no: &str) -> bool {
+        self.student_no
+            .as_deref()
+            .is_none_or(|recorded| recorded == student_no)
+    }
+
+    /// Whether this entry is an attempt to check in given schedule.
+    pub fn is_for(&self, schedule: &Schedule) -> bool {
+        match self.by.as_str() {
+            "id" => self.schedule == schedule.id.as_str(),
+            _ => self.schedule == schedule.uuid.as_str(),
+        }
+    }
+}
+
+/// The history file.
+#[derive(Clone, Debug)]
+pub struct History {
+    /// Path of the file.
+    path: PathBuf,
+}
+
+impl History {
+    /// Opens the history at given path. The file is created on the first record.
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Appends an entry, creating the directory of the file if needed.
+    pub fn append(&self, entry: &CheckInEntry) -> Result<()> {
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    /// Appends an entry, warning instead of failing if it cannot be written, so that check-ins are not affected.
+    pub fn record(&self, entry: &CheckInEntry) {
+        if let Err(e) = self.append(entry) {
+            eprintln!(
+                "Warning: failed to record check-in to {}: {e}",
+                self.path.display()
+            );
+        }
+    }
+
+    /// Loads all entries. A missing file is treated as empty.
+    pub fn load(&self) -> Result<Vec<CheckInEntry>> {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                serde_json::from_str(line)
+                    .with_context(|| format!("Invalid entry at {}:{}", self.path.display(), i + 1))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CheckInEntry, History, Outcome};
+    use chrono::TimeZone;
+    use std::{env, fs, path::PathBuf, process};
+    use ucas_iclass::{Course, Schedule, util::CST_TIMEZONE};
+
+    const UUID: &str = "0123456789abcdef0123456789abcdef";
+
+    fn entry(student_no: Option<&str>, by: &str, schedule: &str) -> CheckInEntry {
+        CheckInEntry {
+            version: 1,
+            time: CST_TIMEZONE
+                .with_ymd_and_hms(2025, 10, 13, 8, 0, 0)
+                .unwrap(),
+            student_no: student_no.map(str::to_string),
+            by: by.to_string(),
+            schedule: schedule.to_string(),
+            course_id: Some("C1".to_string()),
+            course_name: Some("Math".to_string()),
+            timestamp: 1_760_313_600_000,
+            offset_millis: -42,
+            outcome: Outcome::Success,
+            sign_id: Some("1".to_string()),
+            error: None,
+        }
+    }
+
+    fn schedule() -> Schedule {
+        let begin_time = CST_TIMEZONE
+            .with_ymd_and_hms(2025, 10, 13, 8, 0, 0)
+            .unwrap();
+        Schedule {
+            course: Course {
+                course_id: "C1".to_string(),
+                course_name: "Math".to_string(),
+                classroom_name: "A101".to_string(),
+                teacher_name: "T".to_string(),
+            },
+            id: "10".parse().unwrap(),
+            uuid: UUID.parse().unwrap(),
+            checked_in: None,
+            begin_time,
+            end_time: begin_time + chrono::Duration::minutes(95),
+        }
+    }
+
+    /// A path in the temporary directory, removed before use.
+    fn temp_path(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("ucas-iclass-{}-{name}", process::id()));
+        let _ = fs::remove_dir_all(&path);
+        path
+    }
+
+    #[test]
+    fn is_for() {
+        let schedule = schedule();
+        assert!(entry(None, "id", "10").is_for(&schedule));
+        assert!(!entry(None, "id", "9").is_for(&schedule));
+        assert!(!entry(None, "id", UUID).is_for(&schedule));
+        assert!(entry(None, "uuid", UUID).is_for(&schedule));
+        assert!(!entry(None, "uuid", "10").is_for(&schedule));
+    }
+
+    #[test]
+    fn may_be_of() {
+        assert!(entry(Some("2025001"), "id", "10").may_be_of("2025001"));
+        assert!(!entry(Some("2025001"), "id", "10").may_be_of("2025002"));
+        // Entries of unknown accounts may be of any
+        assert!(entry(None, "id", "10").may_be_of("2025002"));
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = temp_path("history-round-trip");
+        let history = History::new(dir.join("nested").join("history.jsonl"));
+        assert!(history.load().unwrap().is_empty());
+        history
+            .append(&entry(Some("2025001"), "uuid", UUID))
+            .unwrap();
+        history.append(&entry(None, "id", "10")).unwrap();
+        let entries = history.load().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].student_no.as_deref(), Some("2025001"));
+        assert_eq!(entries[0].schedule, UUID);
+        assert_eq!(entries[0].time, entry(None, "id", "10").time);
+        assert_eq!(entries[0].offset_millis, -42);
+        assert_eq!(entries[0].outcome, Outcome::Success);
+        assert_eq!(entries[1].student_no, None);
+        assert_eq!(entries[1].by, "id");
+    }
+
+    #[test]
+    fn blank_and_invalid_lines() {
+        let dir = temp_path("history-lines");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("history.jsonl");
+        let line = serde_json::to_string(&entry(None, "id", "10")).unwrap();
+        fs::write(&path, format!("\n{line}\n   \n{line}\n")).unwrap();
+        assert_eq!(History::new(&path).load().unwrap().len(), 2);
+
+        fs::write(&path, format!("{line}\n\n{{\"version\":1}}\n")).unwrap();
+        let error = History::new(&path).load().unwrap_err();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(
+            error.to_string().ends_with("history.jsonl:3"),
+            "unexpected error: {error}"
+        );
+    }
+}
@@ -0,0 +1,260 @@
+//! iCalendar ([RFC 5545](https://www.rfc-editor.org/rfc/rfc5545)) export of schedules.
+//!
+//! Each [`Schedule`] becomes a `VEVENT` whose `UID` is derived from [`Schedule::uuid`], so importing the same schedule again updates the existing event instead of duplicating it. Times are written in the `Asia/Shanghai` time zone, defined by an embedded `VTIMEZONE`.
+
+use super::{Clock, Schedule, Semester, util::CST_TIMEZONE};
+use chrono::{DateTime, Utc};
+
+/// Identifier of the time zone used for event times.
+pub const TZID: &str = "Asia/Shanghai";
+
+/// Product identifier of generated calendars.
+const PRODID: &str = "-//PRO-2684//ucas-iclass//EN";
+/// Domain appended to uuids to form globally unique `UID`s.
+const UID_DOMAIN: &str = "iclass.ucas.edu.cn";
+/// Maximum length of a content line in octets, excluding the line break.
+const MAX_LINE_OCTETS: usize = 75;
+
+/// Generates a calendar with an event for each given schedule, stamped with current time of given clock, like [`IClass::clock`](super::IClass::clock). If the semester is given, teaching weeks are noted in event descriptions.
+#[must_use]
+pub fn calendar<'a, I>(schedules: I, semester: Option<&Semester>, clock: &dyn Clock) -> String
+where
+    I: IntoIterator<Item = &'a Schedule>,
+{
+    calendar_at(schedules, semester, &clock.now())
+}
+
+/// Generates a calendar with an event for each given schedule, stamped with given time. If the semester is given, teaching weeks are noted in event descriptions.
+#[must_use]
+pub fn calendar_at<'a, I>(
+    schedules: I,
+    semester: Option<&Semester>,
+    stamp: &DateTime<Utc>,
+) -> String
+where
+    I: IntoIterator<Item = &'a Schedule>,
+{
+    let mut ics = String::new();
+    push_line(&mut ics, "BEGIN:VCALENDAR");
+    push_line(&mut ics, "VERSION:2.0");
+    push_line(&mut ics, &format!("PRODID:{PRODID}"));
+    push_line(&mut ics, "CALSCALE:GREGORIAN");
+    push_line(&mut ics, "METHOD:PUBLISH");
+    push_line(&mut ics, "X-WR-CALNAME:iClass");
+    push_line(&mut ics, &format!("X-WR-TIMEZONE:{TZID}"));
+    push_timezone(&mut ics);
+    for schedule in schedules {
+        push_event(&mut ics, schedule, semester, stamp);
+    }
+    push_line(&mut ics, "END:VCALENDAR");
+    ics
+}
+
+/// Generates the `VEVENT` of given schedule, stamped with given time. If the semester is given, the teaching week is noted in the description.
+#[must_use]
+pub fn event(schedule: &Schedule, semester: Option<&Semester>, stamp: &DateTime<Utc>) -> String {
+    let mut ics = String::new();
+    push_event(&mut ics, schedule, semester, stamp);
+    ics
+}
+
+/// Appends the `VTIMEZONE` of [`TZID`]. China has not observed daylight saving time since 1991, so a single standard component suffices.
+fn push_timezone(ics: &mut String) {
+    push_line(ics, "BEGIN:VTIMEZONE");
+    push_line(ics, &format!("TZID:{TZID}"));
+    push_line(ics, &format!("X-LIC-LOCATION:{TZID}"));
+    push_line(ics, "BEGIN:STANDARD");
+    push_line(ics, "DTSTART:19700101T000000");
+    push_line(ics, "TZOFFSETFROM:+0800");
+    push_line(ics, "TZOFFSETTO:+0800");
+    push_line(ics, "TZNAME:CST");
+    push_line(ics, "END:STANDARD");
+    push_line(ics, "END:VTIMEZONE");
+}
+
+/// Appends the `VEVENT` of given schedule.
+fn push_event(
+    ics: &mut String,
+    schedule: &Schedule,
+    semester: Option<&Semester>,
+    stamp: &DateTime<Utc>,
+) {
+    let Schedule {
+        course,
+        id,
+        uuid,
+        begin_time,
+        end_time,
+        ..
+    } = schedule;
+    let local = |time: &DateTime<_>| {
+        time.with_timezone(&CST_TIMEZONE)
+            .format("%Y%m%dT%H%M%S")
+            .to_string()
+    };
+    let week = semester
+        .and_then(|semester| {
+            semester.week_of(&begin_time.with_timezone(&CST_TIMEZONE).date_naive())
+        })
+        .map(|week| format!("\nWeek: {week}"))
+        .unwrap_or_default();
+    let description = format!(
+        "Course ID: {}\nTeacher: {}\nSchedule ID: {id}{week}",
+        course.course_id, course.teacher_name
+    );
+    push_line(ics, "BEGIN:VEVENT");
+    push_line(ics, &format!("UID:{uuid}@{UID_DOMAIN}"));
+    push_line(ics, &format!("DTSTAMP:{}", stamp.format("%Y%m%dT%H%M%SZ")));
+    push_line(ics, &format!("DTSTART;TZID={TZID}:{}", local(begin_time)));
+    push_line(ics, &format!("DTEND;TZID={TZID}:{}", local(end_time)));
+    push_line(ics, &format!("SUMMARY:{}", escape(&course.course_name)));
+    push_line(ics, &format!("LOCATION:{}", escape(&course.classroom_name)));
+    push_line(ics, &format!("DESCRIPTION:{}", escape(&description)));
+    push_line(ics, "END:VEVENT");
+}
+
+/// Escapes a `TEXT` value.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Appends a content line terminated by CRLF, folding it so that no line exceeds [`MAX_LINE_OCTETS`] without splitting a UTF-8 character.
+fn push_line(ics: &mut String, line: &str) {
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > MAX_LINE_OCTETS {
+            ics.push_str("\r\n ");
+            octets = 1;
+        }
+        ics.push(c);
+        octets += c.len_utf8();
+    }
+    ics.push_str("\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MAX_LINE_OCTETS, escape, event, push_line};
+    use crate::{Course, Schedule, Semester, util::CST_TIMEZONE};
+    use chrono::{NaiveDate, TimeZone, Utc};
+
+    fn schedule(course_name: &str) -> Schedule {
+        Schedule {
+            course: Course {
+                course_id: "C1".to_string(),
+                course_name: course_name.to_string(),
+                classroom_name: "A101".to_string(),
+                teacher_name: "T".to_string(),
+            },
+            id: "10".parse().unwrap(),
+            uuid: "0123456789abcdef0123456789abcdef".parse().unwrap(),
+            checked_in: None,
+            begin_time: CST_TIMEZONE
+                .with_ymd_and_hms(2025, 10, 13, 8, 0, 0)
+                .unwrap(),
+            end_time: CST_TIMEZONE
+                .with_ymd_and_hms(2025, 10, 13, 9, 35, 0)
+                .unwrap(),
+        }
+    }
+
+    fn semester() -> Semester {
+        Semester {
+            code: "2025F".to_string(),
+            name: "2025秋".to_string(),
+            begin_date: NaiveDate::from_ymd_opt(2025, 9, 1).unwrap(),
+            end_date: NaiveDate::from_ymd_opt(2026, 1, 18).unwrap(),
+            is_current: true,
+        }
+    }
+
+    /// Unfolds content lines and splits them.
+    fn unfold(ics: &str) -> Vec<String> {
+        ics.replace("\r\n ", "")
+            .split_terminator("\r\n")
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(escape(r"a\b;c,d"), r"a\\b\;c\,d");
+        assert_eq!(escape("line\r\nbreak\n"), r"line\nbreak\n");
+        assert_eq!(escape("数学"), "数学");
+    }
+
+    #[test]
+    fn folding() {
+        let mut ics = String::new();
+        push_line(&mut ics, &"a".repeat(MAX_LINE_OCTETS));
+        assert_eq!(ics, format!("{}\r\n", "a".repeat(MAX_LINE_OCTETS)));
+
+        let line = format!("SUMMARY:{}", "数".repeat(60));
+        let mut ics = String::new();
+        push_line(&mut ics, &line);
+        let lines: Vec<_> = ics.split_terminator("\r\n").collect();
+        assert!(lines.len() > 1);
+        for (i, folded) in lines.iter().enumerate() {
+            assert!(folded.len() <= MAX_LINE_OCTETS);
+            assert_eq!(folded.starts_with(' '), i > 0);
+        }
+        assert_eq!(unfold(&ics), [line]);
+    }
+
+    #[test]
+    fn event_properties() {
+        let stamp = Utc.with_ymd_and_hms(2025, 10, 1, 0, 0, 0).unwrap();
+        let ics = event(&schedule("Math, Advanced"), Some(&semester()), &stamp);
+        let lines = unfold(&ics);
+        let property = |name: &str| {
+            lines
+                .iter()
+                .find_map(|line| line.strip_prefix(name))
+                .unwrap_or_else(|| panic!("missing {name}"))
+                .to_string()
+        };
+        assert_eq!(
+            property("UID:"),
+            "0123456789abcdef0123456789abcdef@iclass.ucas.edu.cn"
+        );
+        assert_eq!(property("DTSTAMP:"), "20251001T000000Z");
+        assert_eq!(property("DTSTART;TZID=Asia/Shanghai:"), "20251013T080000");
+        assert_eq!(property("DTEND;TZID=Asia/Shanghai:"), "20251013T093500");
+        assert_eq!(property("SUMMARY:"), r"Math\, Advanced");
+        assert_eq!(
+            property("DESCRIPTION:"),
+            r"Course ID: C1\nTeacher: T\nSchedule ID: 10\nWeek: 7"
+        );
+
+        let without_semester = event(&schedule("Math, Advanced"), None, &stamp);
+        assert!(!without_semester.contains("Week:"));
+    }
+
+    #[test]
+    fn stable_uid() {
+        let stamp = Utc.with_ymd_and_hms(2025, 10, 1, 0, 0, 0).unwrap();
+        let later = Utc.with_ymd_and_hms(2025, 10, 2, 0, 0, 0).unwrap();
+        let uid = |ics: &str| {
+            unfold(ics)
+                .into_iter()
+                .find(|line| line.starts_with("UID:"))
+                .unwrap()
+        };
+        assert_eq!(
+            uid(&event(&schedule("Math"), None, &stamp)),
+            uid(&event(&schedule("Physics"), Some(&semester()), &later)),
+        );
+    }
+}
@@ -6,31 +6,71 @@
 #![warn(clippy::all, clippy::nursery, clippy::pedantic, clippy::cargo)]
 #![allow(clippy::multiple_crate_versions, reason = "Dependency issues")]
 
+#[cfg(feature = "blocking")]
+pub mod blocking;
+mod builder;
 mod checkin;
+mod clock;
+pub mod ics;
 mod login;
+#[cfg(feature = "mock")]
+pub mod mock;
+mod policy;
+mod qr;
 mod query;
+mod target;
+#[cfg(feature = "__tls")]
+mod tls;
+pub mod transport;
 pub mod util;
 
+pub use builder::IClassBuilder;
 pub use checkin::CheckInResult;
-pub use login::UserSessionInfo;
-pub use query::{Course, DailySchedule, Schedule, Semester};
+pub use clock::{Clock, ClockOffset, FixedClock, OffsetClock, SystemClock};
+pub use login::{CredentialProvider, Credentials, UserSessionInfo};
+pub use policy::CheckInPolicy;
+pub use qr::QrPayload;
+pub use query::{Course, DailySchedule, MAX_CONCURRENT_REQUESTS, Schedule, Semester};
+pub use target::{CheckInTarget, ScheduleId, ScheduleUuid};
 
-use cyper::{Client, Error as CyperError};
-use serde::Deserialize;
-use std::fmt::Debug;
+#[cfg(feature = "cyper")]
+use cyper::Error as CyperError;
+use futures_util::lock::Mutex as AsyncMutex;
+use http::StatusCode;
+#[cfg(feature = "reqwest")]
+use reqwest::Error as ReqwestError;
+use serde::{Deserialize, de::DeserializeOwned};
+use std::{
+    fmt::{self, Debug},
+    path::PathBuf,
+    sync::{Mutex, PoisonError},
+};
+#[cfg(any(feature = "cyper", feature = "reqwest"))]
+use transport::HttpTransport;
+use transport::{DefaultTransport, Transport, TransportRequest};
 use url::{ParseError, Url};
 
 /// The root URL of the iClass platform.
 pub const API_ROOT: &str = "https://iclass.ucas.edu.cn:8181/";
 
-/// The iClass struct.
-pub struct IClass {
+/// The iClass struct, sending requests through a [`Transport`].
+pub struct IClass<T = DefaultTransport> {
     /// API root URL.
     api_root: Url,
-    /// The HTTP client.
-    client: Client,
+    /// The transport.
+    transport: T,
     /// User session information.
-    pub user_session: Option<UserSessionInfo>,
+    user_session: Mutex<Option<UserSessionInfo>>,
+    /// Credential provider for logging in again when the session has expired.
+    credential_provider: Option<Box<dyn CredentialProvider>>,
+    /// Held while logging in again, so that concurrent requests with an expired session log in only once.
+    relogin_lock: AsyncMutex<()>,
+    /// File to save the session to after logging in again.
+    session_file: Option<PathBuf>,
+    /// Offset of the server clock to the local clock in milliseconds.
+    clock_offset: i64,
+    /// Source of current time, the system clock if `None`.
+    clock: Option<Box<dyn Clock>>,
 }
 
 /// Possible errors when interacting with the iClass platform.
@@ -39,15 +79,79 @@ pub enum IClassError {
     /// The user has not logged in.
     #[error("user not logged in")]
     NotLoggedIn,
+    /// Invalid parameters (`ERRCODE` 100).
+    #[error("invalid parameter: {0}")]
+    InvalidParameter(ApiErrorInfo),
+    /// The user does not exist (`ERRCODE` 106).
+    #[error("user not found: {0}")]
+    UserNotFound(ApiErrorInfo),
+    /// Wrong password (`ERRCODE` 107).
+    #[error("wrong password: {0}")]
+    WrongPassword(ApiErrorInfo),
+    /// The session has expired or is invalid, and the user should log in again.
+    #[error("session expired: {0}")]
+    SessionExpired(ApiErrorInfo),
+    /// The QR code, or rather the timestamp, is considered outdated by the server.
+    #[error("QR code expired: {0}")]
+    QrCodeExpired(ApiErrorInfo),
+    /// The schedule has already been checked in.
+    #[error("already checked in: {0}")]
+    AlreadyCheckedIn(ApiErrorInfo),
     /// Other API errors.
     #[error("API error: {0}")]
-    ApiError(String),
+    ApiError(ApiErrorInfo),
     /// Cyper-related error.
+    #[cfg(feature = "cyper")]
     #[error("cyper error: {0}")]
     CyperError(#[from] CyperError),
+    /// Reqwest-related error.
+    #[cfg(feature = "reqwest")]
+    #[error("reqwest error: {0}")]
+    ReqwestError(#[from] ReqwestError),
+    /// TLS configuration error, like a client key not matching the certificate.
+    #[cfg(feature = "__tls")]
+    #[error("TLS error: {0}")]
+    TlsError(#[from] rustls::Error),
+    /// Invalid HTTP data, like an invalid header.
+    #[error("HTTP error: {0}")]
+    HttpError(#[from] http::Error),
+    /// Invalid URL.
+    #[error("invalid URL: {0}")]
+    UrlError(#[from] ParseError),
+    /// The proxy rejected the request for missing or wrong credentials.
+    #[error("proxy authentication required")]
+    ProxyAuthRequired,
+    /// Error parsing JSON from the server.
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
     /// Error parsing data from the server.
     #[error("data parsing error")]
     DataParsingError,
+    /// The string is neither a schedule id nor a schedule uuid.
+    #[error("invalid schedule id or uuid: {0}")]
+    InvalidCheckInTarget(String),
+    /// The scanned QR code payload is not recognized.
+    #[error("invalid QR code payload: {0}")]
+    InvalidQrPayload(String),
+    /// The image cannot be read, or contains no iClass check-in QR code.
+    #[cfg(feature = "qr-image")]
+    #[error("QR image error: {0}")]
+    QrImageError(String),
+    /// Failed to obtain credentials from the [`CredentialProvider`].
+    #[error("credential error: {0}")]
+    CredentialError(String),
+    /// IO error, like failing to save the session after logging in again.
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+/// Raw error information returned by the iClass API.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ApiErrorInfo {
+    /// The `ERRCODE` field, if any.
+    pub code: Option<i8>,
+    /// The `ERRMSG` field.
+    pub message: String,
 }
 
 /// Generic response structure from the iClass API.
@@ -61,9 +165,9 @@ where
     pub status: i8,
     /// Optional error code.
     ///
-    /// - 100: 参数错误
-    /// - 106: 用户不存在
-    /// - 107: 密码错误
+    /// - 100: 参数错误 ([`IClassError::InvalidParameter`])
+    /// - 106: 用户不存在 ([`IClassError::UserNotFound`])
+    /// - 107: 密码错误 ([`IClassError::WrongPassword`])
     #[serde(
         rename = "ERRCODE",
         default,
@@ -77,43 +181,247 @@ where
     pub result: Option<T>,
 }
 
+/// # Panics
+///
+/// If the TLS backend fails to initialize, see [`IClass::try_new`].
+#[cfg(any(feature = "cyper", feature = "reqwest"))]
 impl Default for IClass {
     fn default() -> Self {
-        Self::new()
+        Self::try_new().expect("TLS backend should initialize")
     }
 }
 
 impl IClass {
-    /// Creates a new instance of [`IClass`].
-    #[allow(clippy::missing_panics_doc, reason = "URL is constant and valid")]
+    /// Creates a new instance of [`IClass`] with the [`DefaultTransport`].
+    ///
+    /// # Errors
+    ///
+    /// Errors of the transport if the TLS backend fails to initialize.
+    #[cfg(any(feature = "cyper", feature = "reqwest"))]
+    pub fn try_new() -> Result<Self, IClassError> {
+        Self::try_with_api_root(Url::parse(API_ROOT)?)
+    }
+
+    /// Creates a new instance of [`IClass`] with the [`DefaultTransport`] and given API root URL. Proxies are taken from environment variables, see [`IClassBuilder::proxy`].
+    ///
+    /// # Errors
+    ///
+    /// Errors of the transport if the TLS backend fails to initialize.
+    #[cfg(any(feature = "cyper", feature = "reqwest"))]
+    pub fn try_with_api_root(url: Url) -> Result<Self, IClassError> {
+        let transport = DefaultTransport::from_config(&transport::TransportConfig::default())?;
+        Ok(Self::with_transport(url, transport))
+    }
+
+    /// Creates a new instance of [`IClass`] with the [`DefaultTransport`].
+    ///
+    /// # Panics
+    ///
+    /// If the TLS backend fails to initialize.
+    #[cfg(any(feature = "cyper", feature = "reqwest"))]
+    #[deprecated(
+        note = "panics if the TLS backend fails to initialize, use `IClass::try_new` or `IClass::builder` instead"
+    )]
     #[must_use]
     pub fn new() -> Self {
-        Self::with_api_root(Url::parse(API_ROOT).unwrap())
+        Self::try_new().expect("TLS backend should initialize")
     }
 
-    /// Creates a new instance of [`IClass`] with given API root URL.
+    /// Creates a new instance of [`IClass`] with the [`DefaultTransport`] and given API root URL.
+    ///
+    /// # Panics
+    ///
+    /// If the TLS backend fails to initialize.
+    #[cfg(any(feature = "cyper", feature = "reqwest"))]
+    #[deprecated(
+        note = "panics if the TLS backend fails to initialize, use `IClass::try_with_api_root` or `IClass::builder` instead"
+    )]
     #[must_use]
     pub fn with_api_root(url: Url) -> Self {
+        Self::try_with_api_root(url).expect("TLS backend should initialize")
+    }
+
+    /// Creates a builder to configure the transport, session and other options.
+    pub fn builder() -> IClassBuilder {
+        IClassBuilder::new()
+    }
+}
+
+#[allow(
+    clippy::future_not_send,
+    reason = "`Send` whenever the transport is, see `Transport`"
+)]
+impl<T: Transport> IClass<T> {
+    /// Creates a new instance of [`IClass`] with given API root URL and transport.
+    pub fn with_transport(url: Url, transport: T) -> Self {
         Self {
             api_root: url,
-            client: Client::new(),
-            user_session: None,
+            transport,
+            user_session: Mutex::new(None),
+            credential_provider: None,
+            relogin_lock: AsyncMutex::new(()),
+            session_file: None,
+            clock_offset: 0,
+            clock: None,
         }
     }
 
-    /// Gets a reference to user session info, or raises [`IClassError::NotLoggedIn`].
+    /// Gets the transport.
+    pub const fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    /// Gets a copy of current user session info, if logged in.
+    #[must_use]
+    pub fn user_session(&self) -> Option<UserSessionInfo> {
+        self.user_session
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Sets or clears user session info, returning the previous one.
+    pub fn set_user_session(&self, session: Option<UserSessionInfo>) -> Option<UserSessionInfo> {
+        let mut guard = self
+            .user_session
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        std::mem::replace(&mut *guard, session)
+    }
+
+    /// Sets the credential provider used to log in again when the session has expired, or before the first request if not logged in.
+    pub fn set_credential_provider<P>(&mut self, provider: P)
+    where
+        P: CredentialProvider + 'static,
+    {
+        self.credential_provider = Some(Box::new(provider));
+    }
+
+    /// Sets the file to save the new session to after logging in again automatically.
+    pub fn set_session_file<P: Into<PathBuf>>(&mut self, path: Option<P>) {
+        self.session_file = path.map(Into::into);
+    }
+
+    /// Gets a copy of user session info, or raises [`IClassError::NotLoggedIn`].
     ///
     /// # Errors
     ///
     /// [`IClassError::NotLoggedIn`] if the user is not logged in.
-    fn get_user_session(&self) -> Result<&UserSessionInfo, IClassError> {
-        self.user_session.as_ref().ok_or(IClassError::NotLoggedIn)
+    fn get_user_session(&self) -> Result<UserSessionInfo, IClassError> {
+        self.user_session().ok_or(IClassError::NotLoggedIn)
+    }
+
+    /// Sends given request and parses the JSON response.
+    ///
+    /// # Errors
+    ///
+    /// See [`IClassError`].
+    async fn send<R>(&self, request: TransportRequest) -> Result<Response<R>, IClassError>
+    where
+        R: Debug + DeserializeOwned,
+    {
+        let response = self.transport.send(request).await?;
+        if response.status == StatusCode::PROXY_AUTHENTICATION_REQUIRED {
+            return Err(IClassError::ProxyAuthRequired);
+        }
+        Ok(serde_json::from_slice(&response.body)?)
+    }
+
+    /// Sends the request built by `request` from user session info and parses its result. If a [`CredentialProvider`] is set, logs in first when not logged in, and logs in again then retries once when the session has expired.
+    ///
+    /// # Errors
+    ///
+    /// See [`IClassError`].
+    async fn with_session<R>(
+        &self,
+        request: impl Fn(&UserSessionInfo) -> TransportRequest,
+    ) -> Result<R, IClassError>
+    where
+        R: Debug + DeserializeOwned,
+    {
+        if self.credential_provider.is_none() {
+            let session = self.get_user_session()?;
+            return self.send_with_session(request(&session)).await;
+        }
+        let session = match self.get_user_session() {
+            Ok(session) => session,
+            Err(IClassError::NotLoggedIn) => self.relogin(None).await?,
+            Err(e) => return Err(e),
+        };
+        match self.send_with_session(request(&session)).await {
+            Err(IClassError::SessionExpired(_)) => {
+                let session = self.relogin(Some(&session)).await?;
+                self.send_with_session(request(&session)).await
+            }
+            result => result,
+        }
+    }
+
+    /// Sends given request carrying user session info, and converts the response into its result.
+    ///
+    /// # Errors
+    ///
+    /// See [`IClassError`].
+    async fn send_with_session<R>(&self, request: TransportRequest) -> Result<R, IClassError>
+    where
+        R: Debug + DeserializeOwned,
+    {
+        let response: Response<R> = self.send(request).await?;
+        response.into_result()
+    }
+}
+
+impl IClassError {
+    /// Gets the raw error information if this error is returned by the iClass API.
+    #[must_use]
+    pub const fn api_error_info(&self) -> Option<&ApiErrorInfo> {
+        match self {
+            Self::InvalidParameter(info)
+            | Self::UserNotFound(info)
+            | Self::WrongPassword(info)
+            | Self::SessionExpired(info)
+            | Self::QrCodeExpired(info)
+            | Self::AlreadyCheckedIn(info)
+            | Self::ApiError(info) => Some(info),
+            _ => None,
+        }
     }
 }
 
-impl From<ParseError> for IClassError {
-    fn from(e: ParseError) -> Self {
-        Self::CyperError(CyperError::UrlParse(e))
+impl From<ApiErrorInfo> for IClassError {
+    /// Classifies the error by its code, falling back to its message.
+    fn from(info: ApiErrorInfo) -> Self {
+        let message = info.message.as_str();
+        match info.code {
+            Some(100) => Self::InvalidParameter(info),
+            Some(106) => Self::UserNotFound(info),
+            Some(107) => Self::WrongPassword(info),
+            _ if message.contains("二维码")
+                && ["失效", "过期"].iter().any(|s| message.contains(s)) =>
+            {
+                Self::QrCodeExpired(info)
+            }
+            _ if ["已签到", "重复签到"].iter().any(|s| message.contains(s)) => {
+                Self::AlreadyCheckedIn(info)
+            }
+            _ if message.contains("未登录")
+                || (message.contains("登录")
+                    && ["失效", "过期", "重新"].iter().any(|s| message.contains(s))) =>
+            {
+                Self::SessionExpired(info)
+            }
+            _ => Self::ApiError(info),
+        }
+    }
+}
+
+impl fmt::Display for ApiErrorInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self { code, message } = self;
+        match code {
+            Some(code) => write!(f, "{message} (code {code})"),
+            None => write!(f, "{message}"),
+        }
     }
 }
 
@@ -130,11 +438,85 @@ where
         if self.status == 0 {
             self.result.ok_or(IClassError::DataParsingError)
         } else {
-            Err(IClassError::ApiError(if let Some(msg) = self.err_msg {
-                msg
-            } else {
-                format!("Unknown error, {self:?}")
-            }))
+            let message = self
+                .err_msg
+                .clone()
+                .unwrap_or_else(|| format!("Unknown error, {self:?}"));
+            Err(ApiErrorInfo {
+                code: self.err_code,
+                message,
+            }
+            .into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ApiErrorInfo, IClassError};
+
+    fn classify(code: Option<i8>, message: &str) -> IClassError {
+        ApiErrorInfo {
+            code,
+            message: message.to_string(),
+        }
+        .into()
+    }
+
+    #[test]
+    fn classify_by_code() {
+        assert!(matches!(
+            classify(Some(100), "参数错误"),
+            IClassError::InvalidParameter(_)
+        ));
+        assert!(matches!(
+            classify(Some(106), "用户不存在"),
+            IClassError::UserNotFound(_)
+        ));
+        assert!(matches!(
+            classify(Some(107), "密码错误"),
+            IClassError::WrongPassword(_)
+        ));
+        // The code wins over the message
+        assert!(matches!(
+            classify(Some(100), "用户未登录"),
+            IClassError::InvalidParameter(_)
+        ));
+    }
+
+    #[test]
+    fn classify_by_message() {
+        for message in ["二维码已失效", "二维码已过期，请重新扫码"] {
+            assert!(matches!(
+                classify(None, message),
+                IClassError::QrCodeExpired(_)
+            ));
+        }
+        for message in ["您已签到", "请勿重复签到"] {
+            assert!(matches!(
+                classify(None, message),
+                IClassError::AlreadyCheckedIn(_)
+            ));
+        }
+        for message in ["用户未登录", "登录已失效", "登录过期", "请重新登录"] {
+            assert!(matches!(
+                classify(Some(1), message),
+                IClassError::SessionExpired(_)
+            ));
+        }
+    }
+
+    #[test]
+    fn classify_others() {
+        for message in ["系统繁忙", "已失效", "登录成功", ""] {
+            let IClassError::ApiError(info) = classify(None, message) else {
+                panic!("{message:?} should be an unclassified API error");
+            };
+            assert_eq!(info.message, message);
         }
+        assert_eq!(
+            classify(Some(-1), "未知").api_error_info().unwrap().code,
+            Some(-1)
+        );
     }
 }
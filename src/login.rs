@@ -1,44 +1,76 @@
 //! Login related logic.
 
-use super::{IClass, IClassError, Response};
+use super::{
+    IClass, IClassError, Response,
+    transport::{Transport, TransportRequest},
+};
 use serde::{Deserialize, Serialize};
 use std::{
+    fmt,
     fs::File,
     io::{BufReader, BufWriter, Error as IoError, Write},
     path::Path,
 };
 
-impl IClass {
+#[allow(
+    clippy::future_not_send,
+    reason = "`Send` whenever the transport is, see `Transport`"
+)]
+impl<T: Transport> IClass<T> {
     /// Logs in to the iClass platform.
     ///
     /// # Errors
     ///
     /// See [`IClassError`].
-    pub async fn login(&mut self, username: &str, password: &str) -> Result<(), IClassError> {
+    pub async fn login(&self, username: &str, password: &str) -> Result<(), IClassError> {
         // /app/user/login.action
         let url = self.api_root.join("app/user/login.action")?;
-        let response: Response<UserSessionInfo> = self
-            .client
-            .post(url)?
-            .form(&[("phone", username), ("password", password)])?
-            .send()
-            .await?
-            .json()
-            .await?;
+        let request =
+            TransportRequest::post(url).form(&[("phone", username), ("password", password)]);
+        let response: Response<UserSessionInfo> = self.send(request).await?;
         let login_result = response.into_result()?;
-        self.user_session.replace(login_result);
+        self.set_user_session(Some(login_result));
 
         Ok(())
     }
 
+    /// Logs in again with credentials from the [`CredentialProvider`], saving the new session to the session file if set.
+    ///
+    /// Only one re-login runs at a time. If the current session differs from `stale` (or exists at all if `stale` is `None`) once it is our turn, the session has already been renewed by another request, so it is returned directly.
+    ///
+    /// # Errors
+    ///
+    /// See [`IClassError`].
+    pub(super) async fn relogin(
+        &self,
+        stale: Option<&UserSessionInfo>,
+    ) -> Result<UserSessionInfo, IClassError> {
+        let _guard = self.relogin_lock.lock().await;
+        if let Some(current) = self.user_session()
+            && stale.is_none_or(|stale| stale.session_id != current.session_id)
+        {
+            return Ok(current);
+        }
+        let provider = self
+            .credential_provider
+            .as_ref()
+            .ok_or(IClassError::NotLoggedIn)?;
+        let Credentials { username, password } = provider.credentials()?;
+        self.login(&username, &password).await?;
+        if let Some(path) = &self.session_file {
+            self.save_session_to_file(path)?;
+        }
+        self.get_user_session()
+    }
+
     /// Restores user session from given file.
     ///
     /// # Errors
     ///
     /// IO errors during file operations.
-    pub fn restore_session_from_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), IoError> {
+    pub fn restore_session_from_file<P: AsRef<Path>>(&self, path: P) -> Result<(), IoError> {
         let session_info = UserSessionInfo::load_from_file(path)?;
-        self.user_session.replace(session_info);
+        self.set_user_session(Some(session_info));
         Ok(())
     }
 
@@ -48,7 +80,7 @@ impl IClass {
     ///
     /// IO errors during file operations.
     pub fn save_session_to_file<P: AsRef<Path>>(&self, path: P) -> Result<bool, IoError> {
-        let exists = if let Some(session_info) = &self.user_session {
+        let exists = if let Some(session_info) = self.user_session() {
             session_info.save_to_file(path)?;
             true
         } else {
@@ -58,6 +90,51 @@ impl IClass {
     }
 }
 
+/// Username and password used to log in.
+#[derive(Clone)]
+pub struct Credentials {
+    /// The username.
+    pub username: String,
+    /// The password.
+    pub password: String,
+}
+
+/// Provides [`Credentials`] for logging in again when the session has expired.
+///
+/// Implemented for [`Credentials`] itself and for closures returning them.
+pub trait CredentialProvider: Send + Sync {
+    /// Gets the credentials.
+    ///
+    /// # Errors
+    ///
+    /// [`IClassError::CredentialError`] if the credentials cannot be obtained.
+    fn credentials(&self) -> Result<Credentials, IClassError>;
+}
+
+impl CredentialProvider for Credentials {
+    fn credentials(&self) -> Result<Credentials, IClassError> {
+        Ok(self.clone())
+    }
+}
+
+impl<F> CredentialProvider for F
+where
+    F: Fn() -> Result<Credentials, IClassError> + Send + Sync,
+{
+    fn credentials(&self) -> Result<Credentials, IClassError> {
+        self()
+    }
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
+
 /// User session information returned after login.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
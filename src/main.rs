@@ -1,142 +1,995 @@
 #![warn(clippy::all, clippy::nursery, clippy::pedantic, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions, reason = "Dependency issues")]
+#![allow(clippy::future_not_send, reason = "compio runtime is thread-per-core")]
 
+mod accounts;
 mod cli;
+mod config;
+mod history;
+mod output;
+mod password;
+mod report;
+mod watch;
 
-use anyhow::{Result, bail};
-use chrono::{Duration, Utc};
-use cli::{CheckIn, Cli, Courses, Login, Schedule, SubCommands, TimestampOrOffset};
-use std::str::FromStr;
-use ucas_iclass::{IClass, IClassError, Schedule as IClassSchedule, util::get_today};
+use accounts::AccountStore;
+use anyhow::{Error, Result, bail};
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use cli::{
+    Accounts, AccountsAction, AccountsAdd, AccountsDefault, AccountsList, AccountsRemove,
+    Calibrate, CheckIn, Cli, Config, ConfigAction, ConfigGet, ConfigList, ConfigSet, Courses,
+    Export, Login, PASSWORD_ENV, Report, Schedule, Semester, SubCommands, TimestampOrOffset,
+    USERNAME_ENV, Watch,
+};
+use config::{Key, Settings};
+use futures_util::TryStreamExt;
+use history::{CheckInEntry, History};
+use output::{
+    AccountRecord, AccountResultRecord, CheckInRecord, ConfigRecord, LoginRecord, Output,
+    ScheduleRecord,
+};
+use signal_hook::consts::{SIGINT, SIGTERM};
+use std::{
+    env, fs,
+    io::ErrorKind,
+    process::ExitCode,
+    str::FromStr,
+    sync::{Arc, atomic::AtomicBool},
+    time,
+};
+use ucas_iclass::{
+    CheckInPolicy, CheckInTarget, ClockOffset, Course, Credentials, DailySchedule, IClass,
+    IClassError, OffsetClock, QrPayload, Schedule as IClassSchedule, Semester as IClassSemester,
+    SystemClock, UserSessionInfo, ics, util::CST_TIMEZONE,
+};
+use watch::{StatusLog, WatchOptions, Watcher};
+
+/// Number of requests to sample when estimating the server clock offset for check-in.
+const CALIBRATION_SAMPLES: usize = 8;
 
 #[compio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
     let cli: Cli = argh::from_env();
-    let mut iclass = if let Some(api_root) = cli.api_root {
-        IClass::with_api_root(api_root)
-    } else {
-        IClass::new()
-    };
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e:?}");
+            ExitCode::from(exit_code(&e))
+        }
+    }
+}
+
+/// Maps errors to exit codes, so that scripts can tell them apart.
+fn exit_code(error: &Error) -> u8 {
+    match error.downcast_ref::<IClassError>() {
+        Some(
+            IClassError::NotLoggedIn
+            | IClassError::SessionExpired(_)
+            | IClassError::CredentialError(_),
+        ) => 10,
+        Some(IClassError::InvalidParameter(_)) => 11,
+        Some(IClassError::UserNotFound(_)) => 12,
+        Some(IClassError::WrongPassword(_)) => 13,
+        Some(IClassError::QrCodeExpired(_)) => 14,
+        Some(IClassError::AlreadyCheckedIn(_)) => 15,
+        Some(IClassError::ApiError(_)) => 16,
+        Some(
+            IClassError::CyperError(_)
+            | IClassError::TlsError(_)
+            | IClassError::HttpError(_)
+            | IClassError::UrlError(_)
+            | IClassError::ProxyAuthRequired
+            | IClassError::JsonError(_)
+            | IClassError::DataParsingError,
+        ) => 17,
+        Some(
+            IClassError::InvalidCheckInTarget(_)
+            | IClassError::InvalidQrPayload(_)
+            | IClassError::QrImageError(_),
+        ) => 11,
+        #[cfg(feature = "reqwest")]
+        Some(IClassError::ReqwestError(_)) => 17,
+        Some(IClassError::IoError(_)) | None => 1,
+    }
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    let mut settings = Settings::load(cli.profile.clone())?;
+    let output = Output::new(settings.get(Key::Format, cli.format)?.unwrap_or_default());
+    if let SubCommands::Config(args) = &cli.subcommand {
+        return config(&mut settings, output, args);
+    }
+    if settings.explicit && settings.profile().is_none() {
+        bail!("Profile {} not found in the config file", settings.name);
+    }
+    let settings = &settings;
+    match &cli.subcommand {
+        SubCommands::Schedule(args) if args.all_accounts => {
+            check_all_accounts(args.session_file.as_ref(), args.account.as_ref())?;
+            return for_each_account(&cli, settings, output, async |iclass, account| {
+                let args = Schedule {
+                    account: Some(account),
+                    all_accounts: false,
+                    ..args.clone()
+                };
+                schedule(iclass, settings, output, args).await
+            })
+            .await;
+        }
+        SubCommands::CheckIn(args) if args.all_accounts => {
+            check_all_accounts(args.session_file.as_ref(), args.account.as_ref())?;
+            return for_each_account(&cli, settings, output, async |iclass, account| {
+                let args = CheckIn {
+                    account: Some(account),
+                    all_accounts: false,
+                    ..args.clone()
+                };
+                check_in(iclass, settings, output, args).await
+            })
+            .await;
+        }
+        _ => {}
+    }
+    let mut iclass = build_iclass(&cli, settings)?;
 
     match cli.subcommand {
-        SubCommands::Login(Login {
-            username,
-            password,
-            session_file,
-        }) => {
-            iclass.login(&username, &password).await?;
-            iclass.save_session_to_file(&session_file)?;
-            let user_session = iclass.user_session.as_ref().unwrap();
-            println!(
-                "Logged in as {} (student_no={}, id={}), session saved to {}",
-                user_session.real_name, user_session.student_no, user_session.id, session_file
+        SubCommands::Login(args) => login(&iclass, settings, output, args).await,
+        SubCommands::Semester(args) => semester(&mut iclass, settings, output, args).await,
+        SubCommands::Courses(args) => courses(&mut iclass, settings, output, args).await,
+        SubCommands::Schedule(args) => schedule(&mut iclass, settings, output, args).await,
+        SubCommands::CheckIn(args) => check_in(&mut iclass, settings, output, args).await,
+        SubCommands::Calibrate(args) => calibrate(&iclass, output, args).await,
+        SubCommands::Watch(args) => watch(&mut iclass, settings, output, args).await,
+        SubCommands::Export(args) => export(&mut iclass, settings, output, args).await,
+        SubCommands::Report(args) => report(&mut iclass, settings, output, args).await,
+        SubCommands::Accounts(args) => accounts(&iclass, settings, output, args).await,
+        SubCommands::Config(_) => unreachable!("handled above"),
+    }
+}
+
+/// Checks that the options selecting a single session are not used with `--all-accounts`.
+fn check_all_accounts(session_file: Option<&String>, account: Option<&String>) -> Result<()> {
+    if session_file.is_some() || account.is_some() {
+        bail!("--all-accounts cannot be used with -s or --account");
+    }
+    Ok(())
+}
+
+/// Runs a command for every stored account in turn with a fresh [`IClass`] instance, then prints the outcome of each.
+///
+/// Only text output is supported, as the outputs of the accounts and the outcomes would not make up a single document in other formats.
+///
+/// Fails with the error of the first failed account if any, so that the exit code is the one it would have exited with alone.
+async fn for_each_account(
+    cli: &Cli,
+    settings: &Settings,
+    output: Output,
+    run: impl AsyncFn(&mut IClass, String) -> Result<()>,
+) -> Result<()> {
+    if !output.is_text() {
+        bail!("--all-accounts only supports text output, use --account for each account instead");
+    }
+    let store = settings.accounts()?;
+    let sessions = store.list()?;
+    if sessions.is_empty() {
+        bail!(
+            "No accounts stored in {}, add one with `ucas-iclass accounts add`",
+            store.dir().display()
+        );
+    }
+    let default = store.default_account()?;
+    let mut results = Vec::new();
+    let mut first_error = None;
+    for session in sessions {
+        let record = account_record(&store, session, default.as_deref())?;
+        output.note(format!(
+            "Account {} {}:",
+            record.student_no, record.real_name
+        ));
+        let mut iclass = build_iclass(cli, settings)?;
+        let result = run(&mut iclass, record.student_no.clone()).await;
+        results.push(AccountResultRecord {
+            student_no: record.student_no,
+            real_name: record.real_name,
+            exit_code: result.as_ref().map_or_else(exit_code, |()| 0),
+            error: result.as_ref().err().map(|e| format!("{e:#}")),
+        });
+        if let Err(e) = result {
+            first_error.get_or_insert(e);
+        }
+    }
+    output.note("Results:");
+    for result in &results {
+        output.note(format_args!("  {result}"));
+    }
+    let Some(error) = first_error else {
+        return Ok(());
+    };
+    let failed = results
+        .iter()
+        .filter(|result| result.error.is_some())
+        .count();
+    Err(error.context(format!("{failed} of {} account(s) failed", results.len())))
+}
+
+/// Builds the [`IClass`] instance from global options and settings.
+fn build_iclass(cli: &Cli, settings: &Settings) -> Result<IClass> {
+    let mut builder = IClass::builder();
+    if let Some(api_root) = settings.get(Key::ApiRoot, cli.api_root.clone())? {
+        builder = builder.api_root(api_root);
+    }
+    if let Some(timeout) = cli.timeout {
+        builder = builder.timeout(time::Duration::from_secs(timeout));
+    }
+    if let Some(user_agent) = &cli.user_agent {
+        builder = builder.user_agent(user_agent);
+    }
+    for header in &cli.header {
+        let Some((name, value)) = header.split_once(':') else {
+            bail!("Invalid header, expected \"Name: value\": {header}");
+        };
+        builder = builder.header(name.trim(), value.trim());
+    }
+    if let Some(proxy) = settings.proxy(cli.proxy.clone())? {
+        builder = builder.proxy(proxy);
+    }
+    if let Some(no_proxy) = &cli.no_proxy {
+        builder = builder.no_proxy(no_proxy);
+    }
+    if cli.direct {
+        builder = builder.system_proxy(false);
+    }
+    for ca_cert in &cli.ca_cert {
+        builder = builder.root_certificates_pem(fs::read(ca_cert)?);
+    }
+    match (&cli.client_cert, &cli.client_key) {
+        (Some(cert), Some(key)) => {
+            builder = builder.client_identity_pem(fs::read(cert)?, fs::read(key)?);
+        }
+        (None, None) => {}
+        _ => bail!("--client-cert and --client-key must be used together"),
+    }
+    if cli.insecure {
+        eprintln!("Warning: accepting invalid server certificates");
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    if let Some(now) = &cli.now {
+        let Some(start) = NaiveDateTime::parse_from_str(now, "%Y-%m-%d %H:%M:%S")
+            .ok()
+            .and_then(|now| now.and_local_timezone(CST_TIMEZONE).single())
+        else {
+            bail!("Invalid time, expected \"YYYY-MM-DD HH:MM:SS\": {now}");
+        };
+        builder = builder.clock(OffsetClock::starting_at(SystemClock, start.to_utc()));
+    }
+    Ok(builder.build()?)
+}
+
+async fn login(
+    iclass: &IClass,
+    settings: &Settings,
+    output: Output,
+    Login {
+        username,
+        password,
+        password_stdin,
+        password_command,
+        session_file,
+    }: Login,
+) -> Result<()> {
+    let session_file = settings.session_file(session_file)?;
+    let password = password::resolve(password, password_stdin, password_command.as_deref())?;
+    iclass.login(&username, &password).await?;
+    iclass.save_session_to_file(&session_file)?;
+    let user_session = iclass.user_session().unwrap();
+    let text = format!(
+        "Logged in as {} (student_no={}, id={}), session saved to {}",
+        user_session.real_name, user_session.student_no, user_session.id, session_file
+    );
+    let record = LoginRecord {
+        id: user_session.id,
+        real_name: user_session.real_name,
+        student_no: user_session.student_no,
+        session_file,
+    };
+    output.record(&record, text)
+}
+
+async fn semester(
+    iclass: &mut IClass,
+    settings: &Settings,
+    output: Output,
+    Semester {
+        session_file,
+        account,
+    }: Semester,
+) -> Result<()> {
+    restore_session(iclass, settings, session_file, account.as_deref())?;
+    let semesters = iclass.query_semester().await?;
+    output.records(&semesters, "Semesters:")
+}
+
+async fn courses(
+    iclass: &mut IClass,
+    settings: &Settings,
+    output: Output,
+    Courses {
+        semester,
+        session_file,
+        account,
+    }: Courses,
+) -> Result<()> {
+    restore_session(iclass, settings, session_file, account.as_deref())?;
+    let Some(code) = semester else {
+        let courses = iclass.query_courses().await?;
+        return output.records(&courses, "Courses in current semester:");
+    };
+    let semesters = iclass.query_semester().await?;
+    let semester = find_semester(&semesters, &code)?;
+    let heading = format!("Courses in semester {}:", semester.name);
+    if semester.is_current {
+        let courses = iclass.query_courses().await?;
+        return output.records(&courses, heading);
+    }
+    // Courses can only be queried for current semester, so collect them from the schedule instead
+    let semester_schedule: Vec<DailySchedule> = iclass
+        .query_semester_schedule(semester)
+        .try_collect()
+        .await?;
+    let mut courses: Vec<Course> = Vec::new();
+    for schedule in semester_schedule
+        .into_iter()
+        .flat_map(|daily_schedule| daily_schedule.schedules)
+    {
+        if !courses
+            .iter()
+            .any(|course| course.course_id == schedule.course.course_id)
+        {
+            courses.push(schedule.course);
+        }
+    }
+    output.records(&courses, heading)
+}
+
+async fn schedule(
+    iclass: &mut IClass,
+    settings: &Settings,
+    output: Output,
+    Schedule {
+        date,
+        weekly,
+        week,
+        semester,
+        session_file,
+        account,
+        all_accounts: _,
+    }: Schedule,
+) -> Result<()> {
+    restore_session(iclass, settings, session_file, account.as_deref())?;
+    let date = date.unwrap_or_else(|| iclass.clock().today());
+    let semesters = iclass.query_semester().await?;
+    let semester = semester
+        .map(|code| find_semester(&semesters, &code))
+        .transpose()?;
+    if let Some(week) = week {
+        let Some(semester) = semester.or_else(|| semesters.iter().find(|s| s.is_current)) else {
+            bail!("No current semester found, specify one with --semester");
+        };
+        let Some((first_date, _)) = semester.week_range(week) else {
+            bail!(
+                "Week {week} is outside semester {} (weeks 1 ~ {})",
+                semester.name,
+                semester.weeks()
             );
+        };
+        weekly_schedule(iclass, output, &semesters, first_date).await
+    } else if let Some(semester) = semester {
+        if weekly {
+            bail!("-w and --semester cannot be used together, use --week instead");
+        }
+        semester_schedule(iclass, output, semester).await
+    } else if weekly {
+        weekly_schedule(iclass, output, &semesters, date).await
+    } else {
+        let daily_schedule = iclass.query_daily_schedule(&date).await?;
+        let semester = semester_of(&semesters, date);
+        if daily_schedule.is_empty() {
+            warn_outside_semester(&semesters, date);
+        }
+        let week = semester
+            .and_then(|semester| semester.week_of(&date))
+            .map(|week| format!(" (week {week})"))
+            .unwrap_or_default();
+        let records = ScheduleRecord::all(&daily_schedule, semester);
+        output.records(&records, format!("Daily schedule on {date}{week}:"))
+    }
+}
+
+/// Shows the schedule of the week containing given date.
+async fn weekly_schedule(
+    iclass: &IClass,
+    output: Output,
+    semesters: &[IClassSemester],
+    date: NaiveDate,
+) -> Result<()> {
+    let weekly_schedule = iclass.query_weekly_schedule_with_status(&date).await?;
+    let semester = semester_of(semesters, date);
+    if weekly_schedule
+        .iter()
+        .all(|daily_schedule| daily_schedule.schedules.is_empty())
+    {
+        warn_outside_semester(semesters, date);
+    }
+    if output.is_text() {
+        let week = semester
+            .and_then(|semester| Some((semester.week_of(&date)?, &semester.name)))
+            .map(|(week, name)| format!(" (week {week} of {name})"))
+            .unwrap_or_default();
+        println!("Weekly schedule for week of {date}{week}:");
+        for daily_schedule in &weekly_schedule {
+            println!("{daily_schedule}");
         }
-        SubCommands::Courses(Courses { session_file }) => {
-            iclass.restore_session_from_file(&session_file)?;
-            let courses = iclass.query_courses().await?;
-            println!("Courses in current semester:");
-            for course in &courses {
-                println!("  {course}");
+        return Ok(());
+    }
+    let schedules: Vec<_> = weekly_schedule
+        .into_iter()
+        .flat_map(|daily_schedule| daily_schedule.schedules)
+        .collect();
+    output.records(&ScheduleRecord::all(&schedules, semester), "")
+}
+
+/// Shows the whole schedule of given semester.
+async fn semester_schedule(
+    iclass: &IClass,
+    output: Output,
+    semester: &IClassSemester,
+) -> Result<()> {
+    let semester_schedule: Vec<DailySchedule> = iclass
+        .query_semester_schedule(semester)
+        .try_collect()
+        .await?;
+    if output.is_text() {
+        println!("Schedule for semester {semester}:");
+        let mut last_week = None;
+        for daily_schedule in &semester_schedule {
+            if daily_schedule.schedules.is_empty() {
+                continue;
             }
+            let week = semester.week_of(&daily_schedule.date);
+            if week != last_week
+                && let Some(week) = week
+            {
+                println!("Week {week}:");
+                last_week = Some(week);
+            }
+            println!("{daily_schedule}");
         }
-        SubCommands::Schedule(Schedule {
-            date,
-            weekly,
-            session_file,
-        }) => {
-            iclass.restore_session_from_file(&session_file)?;
-            if weekly {
-                let weekly_schedule = iclass.query_weekly_schedule(&date).await?;
-                println!("Weekly schedule for week of {date}:");
-                for daily_schedule in &weekly_schedule {
-                    println!("{daily_schedule}");
-                }
-            } else {
-                let daily_schedule = iclass.query_daily_schedule(&date).await?;
-                println!("Daily schedule on {date}:");
-                for schedule in &daily_schedule {
-                    println!("  {schedule}");
-                }
+        return Ok(());
+    }
+    let schedules: Vec<_> = semester_schedule
+        .into_iter()
+        .flat_map(|daily_schedule| daily_schedule.schedules)
+        .collect();
+    output.records(&ScheduleRecord::all(&schedules, Some(semester)), "")
+}
+
+async fn check_in(
+    iclass: &mut IClass,
+    settings: &Settings,
+    output: Output,
+    CheckIn {
+        id_or_uuid,
+        qr_image,
+        timestamp_or_offset,
+        auto_offset,
+        offset_file,
+        before,
+        after,
+        include_checked_in,
+        history,
+        no_history,
+        session_file,
+        account,
+        all_accounts: _,
+    }: CheckIn,
+) -> Result<()> {
+    restore_session(iclass, settings, session_file, account.as_deref())?;
+    let (target, qr_timestamp) = check_in_target(id_or_uuid, qr_image, output)?;
+    let timestamp_or_offset = match timestamp_or_offset {
+        Some(s) => match TimestampOrOffset::from_str(&s) {
+            Ok(t) => t,
+            Err(()) => bail!("Invalid timestamp or offset: {s}"),
+        },
+        None => match qr_timestamp {
+            Some(timestamp) => {
+                output.note("Using timestamp from QR code");
+                TimestampOrOffset::Timestamp(timestamp)
+            }
+            None => settings.timestamp_offset()?,
+        },
+    };
+    if auto_offset {
+        if matches!(timestamp_or_offset, TimestampOrOffset::Timestamp(_)) {
+            if qr_timestamp.is_some() {
+                bail!(
+                    "--auto-offset cannot be used with the timestamp from QR code, use -t +0 to ignore it"
+                );
             }
+            bail!("--auto-offset cannot be used with an absolute timestamp");
         }
-        SubCommands::CheckIn(CheckIn {
-            id_or_uuid,
-            timestamp_or_offset,
-            session_file,
-        }) => {
-            iclass.restore_session_from_file(&session_file)?;
-            let timestamp_or_offset = match timestamp_or_offset {
-                Some(s) => match TimestampOrOffset::from_str(&s) {
-                    Ok(t) => t,
-                    Err(_) => bail!("Invalid timestamp or offset: {s}"),
-                },
-                None => TimestampOrOffset::default(),
+        let offset = iclass.calibrate_clock(CALIBRATION_SAMPLES).await?;
+        output.note(format!("Estimated server clock offset: {offset}"));
+        if let Some(offset_file) = &offset_file {
+            offset.save_to_file(offset_file)?;
+            output.note(format!("Clock offset saved to {offset_file}"));
+        }
+    } else if let Some(offset_file) = &offset_file {
+        let offset = ClockOffset::load_from_file(offset_file)?;
+        iclass.set_clock_offset(offset.offset_millis);
+        output.note(format!("Using saved server clock offset: {offset}"));
+    }
+    let timestamp = timestamp_or_offset.resolve(iclass.server_timestamp_millis());
+    output.note(format!("Using timestamp (ms): {timestamp}"));
+    let history = (!no_history)
+        .then(|| settings.history(history))
+        .transpose()?
+        .map(History::new);
+    if let Some(target) = target {
+        let result = iclass.check_in(&target, timestamp).await;
+        if let Some(history) = &history {
+            history.record(&CheckInEntry::new(
+                iclass,
+                target.kind(),
+                target.as_str(),
+                None,
+                timestamp,
+                &result,
+            ));
+        }
+        let result = result?;
+        let by = target.kind();
+        let text = format!(
+            "Check-in by {by} for schedule {}: {result}",
+            target.as_str()
+        );
+        let record = CheckInRecord {
+            by,
+            schedule: target.as_str().to_string(),
+            course_name: None,
+            timestamp,
+            result,
+        };
+        output.record(&record, text)
+    } else {
+        // no id or uuid provided, try to determine current schedule
+        let policy = CheckInPolicy {
+            include_checked_in: settings.include_checked_in(include_checked_in)?,
+            ..settings.check_in_policy(before, after)?
+        };
+        let schedule = current_schedule(iclass, &policy, output).await?;
+        // Just use uuid for check-in
+        let uuid = &schedule.uuid;
+        let name = &schedule.course.course_name;
+        let result = iclass.check_in_by_uuid(&schedule.uuid, timestamp).await;
+        if let Some(history) = &history {
+            history.record(&CheckInEntry::new(
+                iclass,
+                "uuid",
+                uuid.as_str(),
+                Some(&schedule.course),
+                timestamp,
+                &result,
+            ));
+        }
+        let result = result?;
+        let text = format!("Check-in by uuid for current schedule {uuid} ({name}): {result}");
+        let record = CheckInRecord {
+            by: "uuid",
+            schedule: uuid.to_string(),
+            course_name: Some(name.clone()),
+            timestamp,
+            result,
+        };
+        output.record(&record, text)
+    }
+}
+
+/// Determines the schedule to check in from the positional argument or the QR code image, along with the timestamp in the QR code if any.
+fn check_in_target(
+    id_or_uuid: Option<String>,
+    qr_image: Option<String>,
+    output: Output,
+) -> Result<(Option<CheckInTarget>, Option<u128>)> {
+    Ok(match (id_or_uuid, qr_image) {
+        (Some(_), Some(_)) => bail!("Give either the schedule or --qr-image, not both"),
+        (Some(s), None) => {
+            let (target, timestamp) = parse_check_in_target(&s)?;
+            (Some(target), timestamp)
+        }
+        (None, Some(path)) => {
+            let payload = QrPayload::from_image(&fs::read(&path)?)?;
+            output.note(format!(
+                "Decoded QR code from {path}: schedule {}",
+                payload.target
+            ));
+            (Some(payload.target), payload.timestamp)
+        }
+        (None, None) => (None, None),
+    })
+}
+
+/// Parses the schedule to check in, with the timestamp embedded if it is a scanned QR code payload.
+fn parse_check_in_target(s: &str) -> Result<(CheckInTarget, Option<u128>)> {
+    // Anything other than a schedule id or uuid should be a QR code payload
+    if let Ok(target) = s.parse() {
+        return Ok((target, None));
+    }
+    let QrPayload { target, timestamp } = s.parse()?;
+    Ok((target, timestamp))
+}
+
+/// Picks the highest ranked schedule eligible for check-in under given policy.
+async fn current_schedule(
+    iclass: &IClass,
+    policy: &CheckInPolicy,
+    output: Output,
+) -> Result<IClassSchedule> {
+    let mut schedules = iclass.current_schedules(policy).await?.into_iter();
+    let Some(schedule) = schedules.next() else {
+        bail!("No current schedule eligible for check-in");
+    };
+    if schedules.len() > 0 {
+        output.note(format!(
+            "Picking the first of {} eligible schedules",
+            schedules.len() + 1
+        ));
+    }
+    Ok(schedule)
+}
+
+fn config(settings: &mut Settings, output: Output, Config { action }: &Config) -> Result<()> {
+    let Some(path) = settings.path.clone() else {
+        bail!("Cannot locate the config directory, set XDG_CONFIG_HOME");
+    };
+    let name = settings.name.clone();
+    match action {
+        ConfigAction::Get(ConfigGet { key }) => {
+            let Some(value) = settings.profile().and_then(|profile| profile.get(*key)) else {
+                bail!("{key} is not set in profile {name}");
             };
-            let timestamp = timestamp_or_offset.resolve();
-            println!("Using timestamp (ms): {timestamp}");
-            match id_or_uuid {
-                // id or uuid provided, determine which one it is
-                Some(id_or_uuid) => {
-                    // id is all numeric, uuid is all hexadecimal and 32 characters long
-                    let (type_, result) = if id_or_uuid.len() == 32
-                        && id_or_uuid.chars().all(|c| c.is_ascii_hexdigit())
-                    {
-                        (
-                            "uuid",
-                            iclass.check_in_by_uuid(&id_or_uuid, timestamp).await?,
-                        )
-                    } else if id_or_uuid.chars().all(char::is_numeric) {
-                        ("id", iclass.check_in_by_id(&id_or_uuid, timestamp).await?)
-                    } else {
-                        bail!("Invalid id or uuid format: {id_or_uuid}");
-                    };
-                    println!("Check-in by {type_} for schedule {id_or_uuid}: {result}");
-                }
-                // no id or uuid provided, try to determine current schedule
-                None => {
-                    let current_schedule = determine_current_schedule(&mut iclass).await?;
-                    let Some(schedule) = current_schedule else {
-                        bail!("No current schedule eligible for check-in");
-                    };
-                    // Just use uuid for check-in
-                    let uuid = &schedule.uuid;
-                    let name = &schedule.course.course_name;
-                    let result = iclass.check_in_by_uuid(&schedule.uuid, timestamp).await?;
-                    println!("Check-in by uuid for current schedule {uuid} ({name}): {result}");
-                }
+            let record = ConfigRecord {
+                profile: name,
+                key: key.name(),
+                value: value.clone(),
             };
+            output.record(&record, value)
+        }
+        ConfigAction::Set(ConfigSet { key, value }) => {
+            let profile = settings.config.profiles.entry(name.clone()).or_default();
+            profile.set(*key, value.as_deref())?;
+            settings.config.save(&path)?;
+            let action = if value.is_some() { "Set" } else { "Removed" };
+            output.note(format!(
+                "{action} {key} in profile {name} of {}",
+                path.display()
+            ));
+            Ok(())
+        }
+        ConfigAction::List(ConfigList {}) => {
+            let records: Vec<_> = settings
+                .profile()
+                .into_iter()
+                .flat_map(|profile| {
+                    Key::ALL.into_iter().filter_map(|key| {
+                        Some(ConfigRecord {
+                            profile: name.clone(),
+                            key: key.name(),
+                            value: profile.get(key)?,
+                        })
+                    })
+                })
+                .collect();
+            output.records(&records, format!("Profile {name} in {}:", path.display()))
         }
     }
+}
 
+async fn accounts(
+    iclass: &IClass,
+    settings: &Settings,
+    output: Output,
+    Accounts { action }: Accounts,
+) -> Result<()> {
+    let store = settings.accounts()?;
+    match action {
+        AccountsAction::List(AccountsList {}) => {
+            let default = store.default_account()?;
+            let records = store
+                .list()?
+                .into_iter()
+                .map(|session| account_record(&store, session, default.as_deref()))
+                .collect::<Result<Vec<_>>>()?;
+            output.records(&records, format!("Accounts in {}:", store.dir().display()))
+        }
+        AccountsAction::Add(AccountsAdd {
+            username,
+            password,
+            password_stdin,
+            password_command,
+        }) => {
+            let password =
+                password::resolve(password, password_stdin, password_command.as_deref())?;
+            iclass.login(&username, &password).await?;
+            let session = iclass.user_session().unwrap();
+            store.add(&session)?;
+            let default = store.default_account()?;
+            let record = account_record(&store, session, default.as_deref())?;
+            let text = format!(
+                "Added account {} {} (id={}), session saved to {}{}",
+                record.student_no,
+                record.real_name,
+                record.id,
+                record.session_file,
+                if record.default {
+                    " as the default"
+                } else {
+                    ""
+                }
+            );
+            output.record(&record, text)
+        }
+        AccountsAction::Remove(AccountsRemove { student_no }) => {
+            store.remove(&student_no)?;
+            output.note(format!(
+                "Removed account {student_no} from {}",
+                store.dir().display()
+            ));
+            Ok(())
+        }
+        AccountsAction::Default(AccountsDefault { student_no }) => {
+            store.set_default(&student_no)?;
+            output.note(format!("Account {student_no} is now the default"));
+            Ok(())
+        }
+    }
+}
+
+/// Describes a stored account.
+fn account_record(
+    store: &AccountStore,
+    session: UserSessionInfo,
+    default: Option<&str>,
+) -> Result<AccountRecord> {
+    Ok(AccountRecord {
+        session_file: store
+            .session_file(&session.student_no)?
+            .display()
+            .to_string(),
+        default: default == Some(session.student_no.as_str()),
+        student_no: session.student_no,
+        real_name: session.real_name,
+        id: session.id,
+    })
+}
+
+async fn calibrate(
+    iclass: &IClass,
+    output: Output,
+    Calibrate {
+        samples,
+        offset_file,
+    }: Calibrate,
+) -> Result<()> {
+    let offset = iclass.estimate_clock_offset(samples).await?;
+    output.record(&offset, format!("Estimated server clock offset: {offset}"))?;
+    if let Some(offset_file) = &offset_file {
+        offset.save_to_file(offset_file)?;
+        output.note(format!("Clock offset saved to {offset_file}"));
+    }
     Ok(())
 }
 
-async fn determine_current_schedule(
+async fn watch(
     iclass: &mut IClass,
-) -> Result<Option<IClassSchedule>, IClassError> {
-    let today = get_today();
-    let daily_schedule = iclass.query_daily_schedule(&today).await?;
-    let now = Utc::now();
-    let allowed_checkin_duration = Duration::minutes(30);
-
-    let mut candidate: Option<IClassSchedule> = None;
-    for schedule in daily_schedule {
-        let IClassSchedule {
-            begin_time,
-            end_time,
-            ..
-        } = &schedule;
-        let checkin_time = *begin_time - allowed_checkin_duration;
-        // The schedule should be within the check-in time window,
-        if now >= checkin_time && now <= *end_time
-            // not checked in yet,
-            && !schedule.checked_in
-            // and we should choose the schedule with smallest id if multiple available
-            && candidate.as_ref().map_or(true, |s| schedule.id < s.id)
-        {
-            candidate.replace(schedule);
+    settings: &Settings,
+    output: Output,
+    Watch {
+        before,
+        after,
+        jitter,
+        refresh,
+        offset_file,
+        log_file,
+        history,
+        no_history,
+        session_file,
+        account,
+    }: Watch,
+) -> Result<()> {
+    restore_session(iclass, settings, session_file, account.as_deref())?;
+    if let Some(offset_file) = &offset_file {
+        let offset = ClockOffset::load_from_file(offset_file)?;
+        iclass.set_clock_offset(offset.offset_millis);
+        output.note(format!("Using saved server clock offset: {offset}"));
+    }
+    let shutdown = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(SIGINT, Arc::clone(&shutdown))?;
+    signal_hook::flag::register(SIGTERM, Arc::clone(&shutdown))?;
+    let options = WatchOptions {
+        policy: settings.check_in_policy(before, after)?,
+        jitter: Duration::seconds(jitter.into()),
+        refresh: Duration::minutes(refresh.max(1).into()),
+    };
+    Watcher::new(
+        iclass,
+        options,
+        StatusLog::new(output, log_file.as_deref(), iclass.clock())?,
+        (!no_history)
+            .then(|| settings.history(history))
+            .transpose()?
+            .map(History::new),
+        shutdown,
+    )
+    .run()
+    .await
+}
+
+async fn export(
+    iclass: &mut IClass,
+    settings: &Settings,
+    output: Output,
+    Export {
+        ics,
+        date,
+        weekly,
+        semester,
+        output: output_file,
+        session_file,
+        account,
+    }: Export,
+) -> Result<()> {
+    if !ics {
+        bail!("No export format specified, use --ics");
+    }
+    if weekly && semester {
+        bail!("-w and -S cannot be used together");
+    }
+    restore_session(iclass, settings, session_file, account.as_deref())?;
+    let date = date.unwrap_or_else(|| iclass.clock().today());
+    let semesters = iclass.query_semester().await?;
+    let semester_of_date = semester_of(&semesters, date);
+    let (description, schedules) = if semester {
+        let Some(semester) = semester_of_date else {
+            bail!("No semester contains {date}");
+        };
+        let semester_schedule: Vec<DailySchedule> = iclass
+            .query_semester_schedule(semester)
+            .try_collect()
+            .await?;
+        let schedules = semester_schedule
+            .into_iter()
+            .flat_map(|daily_schedule| daily_schedule.schedules)
+            .collect();
+        (format!("semester {}", semester.name), schedules)
+    } else if weekly {
+        let weekly_schedule = iclass.query_weekly_schedule(&date).await?;
+        let schedules = weekly_schedule
+            .into_iter()
+            .flat_map(|daily_schedule| daily_schedule.schedules)
+            .collect();
+        (format!("week of {date}"), schedules)
+    } else {
+        (date.to_string(), iclass.query_daily_schedule(&date).await?)
+    };
+    let calendar = ics::calendar(&schedules, semester_of_date, iclass.clock());
+    if let Some(output_file) = &output_file {
+        fs::write(output_file, calendar)?;
+        output.note(format!(
+            "Exported {} schedule(s) for {description} to {output_file}",
+            schedules.len()
+        ));
+    } else {
+        print!("{calendar}");
+    }
+    Ok(())
+}
+
+async fn report(
+    iclass: &mut IClass,
+    settings: &Settings,
+    output: Output,
+    Report {
+        from,
+        to,
+        semester,
+        history,
+        session_file,
+        account,
+    }: Report,
+) -> Result<()> {
+    restore_session(iclass, settings, session_file, account.as_deref())?;
+    let semesters = iclass.query_semester().await?;
+    let semester = if let Some(code) = semester {
+        find_semester(&semesters, &code)?
+    } else if let Some(current) = semesters.iter().find(|semester| semester.is_current) {
+        current
+    } else {
+        bail!("No current semester found, specify one with --semester");
+    };
+    let from = from.unwrap_or(semester.begin_date);
+    let to = to.unwrap_or_else(|| semester.end_date.min(iclass.clock().today()));
+    if from > to {
+        bail!("Nothing to report, {from} is after {to}");
+    }
+    let mut entries = History::new(settings.history(history)?).load()?;
+    let mut schedule_range: Vec<DailySchedule> =
+        iclass.query_schedule_range(from, to).try_collect().await?;
+    iclass.fill_check_in_status(&mut schedule_range).await?;
+    let schedules: Vec<_> = schedule_range
+        .into_iter()
+        .flat_map(|daily_schedule| daily_schedule.schedules)
+        .collect();
+    // Courses can only be queried for current semester, otherwise they are collected from the schedule
+    let courses = if semester.is_current {
+        iclass.query_courses().await?
+    } else {
+        Vec::new()
+    };
+    // The history may be shared by multiple accounts
+    if let Some(session) = iclass.user_session() {
+        entries.retain(|entry| entry.may_be_of(&session.student_no));
+    }
+    let records = report::attendance(&courses, &schedules, &entries, iclass.clock().now());
+    output.records(&records, format!("Attendance from {from} to {to}:"))
+}
+
+/// Finds the semester with given code.
+fn find_semester<'a>(semesters: &'a [IClassSemester], code: &str) -> Result<&'a IClassSemester> {
+    let Some(semester) = semesters.iter().find(|semester| semester.code == code) else {
+        bail!("Unknown semester code: {code}, see `ucas-iclass semester` for available ones");
+    };
+    Ok(semester)
+}
+
+/// Finds the semester containing given date.
+fn semester_of(semesters: &[IClassSemester], date: NaiveDate) -> Option<&IClassSemester> {
+    semesters.iter().find(|semester| semester.contains(&date))
+}
+
+/// Warns if given date is outside the current semester, explaining an empty schedule.
+fn warn_outside_semester(semesters: &[IClassSemester], date: NaiveDate) {
+    match semesters.iter().find(|semester| semester.is_current) {
+        Some(current) if !current.contains(&date) => {
+            eprintln!(
+                "Warning: {date} is outside the current semester {} ({} ~ {}), use --semester to query another one",
+                current.name, current.begin_date, current.end_date
+            );
         }
+        Some(_) => {}
+        None => eprintln!("Warning: no current semester found"),
     }
+}
 
-    Ok(candidate)
+/// Restores session from given file or stored account, see [`Settings::load_session_file`].
+///
+/// If credentials are provided via environment variables and the session does not belong to a stored account, also enables logging in again automatically when the session has expired, saving the renewed session to the file. In this case, a missing session file is tolerated.
+fn restore_session(
+    iclass: &mut IClass,
+    settings: &Settings,
+    session_file: Option<String>,
+    account: Option<&str>,
+) -> Result<()> {
+    let (session_file, stored) = settings.load_session_file(session_file, account)?;
+    // The credentials belong to a single account, so they are not used for stored accounts
+    let (Ok(username), Ok(password), false) =
+        (env::var(USERNAME_ENV), env::var(PASSWORD_ENV), stored)
+    else {
+        iclass.restore_session_from_file(&session_file)?;
+        return Ok(());
+    };
+    iclass.set_credential_provider(Credentials { username, password });
+    iclass.set_session_file(Some(&session_file));
+    match iclass.restore_session_from_file(&session_file) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
 }
@@ -0,0 +1,530 @@
+//! A mock iClass server for offline testing.
+//!
+//! The [`MockServer`] listens on a random local port and implements the endpoints used by [`IClass`](super::IClass), wrapping results in the same `STATUS`/`ERRCODE`/`ERRMSG`/`result` envelope as the real platform. Its [`MockState`] is shared with the server thread, so tests can script users, semesters, courses, schedules and injected errors, and inspect sign status and recorded requests afterwards.
+
+use super::{
+    Course, Schedule, Semester, UserSessionInfo,
+    util::{CST_TIMEZONE, format_date_to_str, format_datetime_to_str},
+};
+use chrono::{Datelike, Duration, NaiveDate, TimeDelta, Utc};
+use serde_json::{Value, json};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    io::Error as IoError,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    thread::{self, JoinHandle},
+};
+use tiny_http::{Header, Request, Response, Server};
+use url::{Url, form_urlencoded};
+
+/// Error message returned when the session id is missing or unknown.
+pub const SESSION_EXPIRED_MSG: &str = "登录已失效，请重新登录！";
+/// Error message returned when the check-in timestamp is out of tolerance.
+pub const QR_CODE_EXPIRED_MSG: &str = "二维码已失效！";
+/// Error message returned when the schedule has already been checked in.
+pub const ALREADY_CHECKED_IN_MSG: &str = "您已签到，请勿重复签到！";
+
+/// Endpoints implemented by the [`MockServer`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MockEndpoint {
+    /// `app/user/login.action`
+    Login,
+    /// `app/course/get_base_school_year.action`
+    Semester,
+    /// `app/my/get_my_course.action`
+    Courses,
+    /// `app/course/get_stu_course_sched.action`
+    DailySchedule,
+    /// `app/course/get_stu_course_sched_week.action`
+    WeeklySchedule,
+    /// `app/course/stu_scan_sign.action`
+    CheckIn,
+}
+
+/// A user known to the [`MockServer`].
+#[derive(Clone, Debug)]
+pub struct MockUser {
+    /// Username used to log in, the `phone` form field.
+    pub username: String,
+    /// Password used to log in.
+    pub password: String,
+    /// Session information returned on successful login. The session ID is replaced on every login.
+    pub info: UserSessionInfo,
+}
+
+/// An error to be returned by the [`MockServer`] instead of a normal response.
+#[derive(Clone, Debug)]
+pub struct MockError {
+    /// The `ERRCODE` field, if any.
+    pub code: Option<i8>,
+    /// The `ERRMSG` field.
+    pub message: String,
+}
+
+/// A request received by the [`MockServer`].
+#[derive(Clone, Debug)]
+pub struct MockRequest {
+    /// The endpoint requested.
+    pub endpoint: MockEndpoint,
+    /// The `sessionId` header, if any.
+    pub session_id: Option<String>,
+    /// Form fields and query parameters combined.
+    pub params: HashMap<String, String>,
+    /// Whether the request was sent to the server acting as an HTTP proxy, i.e. with an absolute URL as the request target.
+    pub proxied: bool,
+}
+
+/// Scriptable state of the [`MockServer`].
+#[derive(Debug)]
+pub struct MockState {
+    /// Registered users.
+    pub users: Vec<MockUser>,
+    /// Semesters returned by [`MockEndpoint::Semester`].
+    pub semesters: Vec<Semester>,
+    /// Courses returned by [`MockEndpoint::Courses`].
+    pub courses: Vec<Course>,
+    /// Schedules shared by all users. Their `checked_in` field is ignored in favor of [`signs`](Self::signs).
+    pub schedules: Vec<Schedule>,
+    /// Sign records, as `(user id, schedule uuid)` pairs.
+    pub signs: HashSet<(String, String)>,
+    /// Offset of the server clock to the real clock in milliseconds, affecting the `Date` header and timestamp validation.
+    pub clock_offset_millis: i64,
+    /// Maximum allowed difference between check-in timestamps and the server clock in milliseconds. `None` to accept any timestamp.
+    pub timestamp_tolerance_millis: Option<u64>,
+    /// Username and password required for requests sent to the server acting as an HTTP proxy. `None` to accept proxied requests without authentication.
+    pub proxy_auth: Option<(String, String)>,
+    /// Requests received so far.
+    pub requests: Vec<MockRequest>,
+    /// Queued errors for each endpoint.
+    errors: HashMap<MockEndpoint, VecDeque<MockError>>,
+    /// Active sessions, mapping session ids to user ids.
+    sessions: HashMap<String, String>,
+    /// Counter for generating session ids and sign ids.
+    counter: u64,
+}
+
+/// A mock iClass server running on a background thread.
+pub struct MockServer {
+    /// The underlying HTTP server.
+    server: Arc<Server>,
+    /// The shared state.
+    state: Arc<Mutex<MockState>>,
+    /// API root URL of the server.
+    api_root: Url,
+    /// Handle of the background thread.
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Default for MockState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockState {
+    /// Creates an empty state.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            users: Vec::new(),
+            semesters: Vec::new(),
+            courses: Vec::new(),
+            schedules: Vec::new(),
+            signs: HashSet::new(),
+            clock_offset_millis: 0,
+            timestamp_tolerance_millis: None,
+            proxy_auth: None,
+            requests: Vec::new(),
+            errors: HashMap::new(),
+            sessions: HashMap::new(),
+            counter: 0,
+        }
+    }
+
+    /// Registers a user with given credentials.
+    pub fn add_user(&mut self, username: &str, password: &str, info: UserSessionInfo) {
+        self.users.push(MockUser {
+            username: username.to_string(),
+            password: password.to_string(),
+            info,
+        });
+    }
+
+    /// Queues an error to be returned by the next request to given endpoint.
+    pub fn inject_error(&mut self, endpoint: MockEndpoint, code: Option<i8>, message: &str) {
+        self.errors
+            .entry(endpoint)
+            .or_default()
+            .push_back(MockError {
+                code,
+                message: message.to_string(),
+            });
+    }
+
+    /// Invalidates all active sessions, so that subsequent requests fail until logging in again.
+    pub fn expire_sessions(&mut self) {
+        self.sessions.clear();
+    }
+
+    /// Whether given proxied request carries the required `Proxy-Authorization` header.
+    fn is_proxy_authorized(&self, request: &Request) -> bool {
+        let Some((username, password)) = &self.proxy_auth else {
+            return true;
+        };
+        let expected = format!("Basic {}", base64(&format!("{username}:{password}")));
+        request
+            .headers()
+            .iter()
+            .any(|h| h.field.equiv("Proxy-Authorization") && h.value.as_str() == expected)
+    }
+
+    /// Whether the user with given id has checked in the schedule with given uuid.
+    #[must_use]
+    pub fn is_checked_in(&self, user_id: &str, schedule_uuid: &str) -> bool {
+        self.signs
+            .contains(&(user_id.to_string(), schedule_uuid.to_string()))
+    }
+
+    /// Handles a request, returning the envelope to respond with.
+    fn handle(&mut self, request: MockRequest) -> Value {
+        let endpoint = request.endpoint;
+        let result =
+            if let Some(error) = self.errors.get_mut(&endpoint).and_then(VecDeque::pop_front) {
+                Err(error)
+            } else {
+                match endpoint {
+                    MockEndpoint::Login => self.login(&request.params),
+                    MockEndpoint::Semester => self.semester(&request.params),
+                    MockEndpoint::Courses => self
+                        .authorize(&request)
+                        .map(|_| Value::Array(self.courses.iter().map(course_json).collect())),
+                    MockEndpoint::DailySchedule => self.daily_schedule(&request),
+                    MockEndpoint::WeeklySchedule => self.weekly_schedule(&request),
+                    MockEndpoint::CheckIn => self.check_in(&request),
+                }
+            };
+        self.requests.push(request);
+        match result {
+            Ok(result) => json!({ "STATUS": "0", "result": result }),
+            Err(error) => failure(error.code, &error.message),
+        }
+    }
+
+    fn login(&mut self, params: &HashMap<String, String>) -> Result<Value, MockError> {
+        let (Some(username), Some(password)) = (params.get("phone"), params.get("password")) else {
+            return Err(error(Some(100), "参数错误"));
+        };
+        let Some(user) = self.users.iter().find(|u| &u.username == username) else {
+            return Err(error(Some(106), "用户不存在"));
+        };
+        if &user.password != password {
+            return Err(error(Some(107), "密码错误"));
+        }
+        let mut info = user.info.clone();
+        self.counter += 1;
+        info.session_id = format!("mock-session-{}", self.counter);
+        self.sessions
+            .insert(info.session_id.clone(), info.id.clone());
+        Ok(json!({
+            "id": info.id,
+            "sessionId": info.session_id,
+            "realName": info.real_name,
+            "studentNo": info.student_no,
+        }))
+    }
+
+    fn semester(&self, params: &HashMap<String, String>) -> Result<Value, MockError> {
+        let known = params
+            .get("userId")
+            .is_some_and(|id| self.users.iter().any(|u| &u.info.id == id));
+        if !known {
+            return Err(error(Some(100), "参数错误"));
+        }
+        Ok(Value::Array(
+            self.semesters.iter().map(semester_json).collect(),
+        ))
+    }
+
+    fn daily_schedule(&self, request: &MockRequest) -> Result<Value, MockError> {
+        let user_id = self.authorize(request)?;
+        let date = parse_date_param(&request.params)?;
+        Ok(Value::Array(
+            self.schedules_on(date)
+                .map(|s| schedule_json(s, self.is_checked_in(&user_id, s.uuid.as_str())))
+                .collect(),
+        ))
+    }
+
+    fn weekly_schedule(&self, request: &MockRequest) -> Result<Value, MockError> {
+        self.authorize(request)?;
+        let date = parse_date_param(&request.params)?;
+        let monday = date - Duration::days(i64::from(date.weekday().num_days_from_monday()));
+        let days = monday.iter_days().take(7).map(|day| {
+            // Sign status is not reflected in weekly schedules
+            let schedules: Vec<_> = self
+                .schedules_on(day)
+                .map(|s| schedule_json(s, false))
+                .collect();
+            json!({ "dateStr": format_date_to_str(&day), "schedData": schedules })
+        });
+        Ok(Value::Array(days.collect()))
+    }
+
+    fn check_in(&mut self, request: &MockRequest) -> Result<Value, MockError> {
+        let user_id = self.authorize(request)?;
+        let params = &request.params;
+        let schedule = if let Some(uuid) = params.get("timeTableId") {
+            self.schedules.iter().find(|s| s.uuid.as_str() == uuid)
+        } else if let Some(id) = params.get("courseSchedId") {
+            self.schedules.iter().find(|s| s.id.as_str() == id)
+        } else {
+            return Err(error(Some(100), "参数错误"));
+        };
+        let Some(uuid) = schedule.map(|s| s.uuid.to_string()) else {
+            return Err(error(None, "课程不存在！"));
+        };
+        let Some(timestamp) = params.get("timestamp").and_then(|t| t.parse::<i64>().ok()) else {
+            return Err(error(Some(100), "参数错误"));
+        };
+        if let Some(tolerance) = self.timestamp_tolerance_millis {
+            let server_now = self.server_now().timestamp_millis();
+            if timestamp.abs_diff(server_now) > tolerance {
+                return Err(error(None, QR_CODE_EXPIRED_MSG));
+            }
+        }
+        if !self.signs.insert((user_id, uuid)) {
+            return Err(error(None, ALREADY_CHECKED_IN_MSG));
+        }
+        self.counter += 1;
+        Ok(json!({ "stuSignId": self.counter.to_string(), "stuSignStatus": "1" }))
+    }
+
+    /// Checks the session id of given request, returning the user id.
+    fn authorize(&self, request: &MockRequest) -> Result<String, MockError> {
+        request
+            .session_id
+            .as_ref()
+            .and_then(|session_id| self.sessions.get(session_id))
+            .filter(|user_id| request.params.get("id") == Some(*user_id))
+            .cloned()
+            .ok_or_else(|| error(None, SESSION_EXPIRED_MSG))
+    }
+
+    fn schedules_on(&self, date: NaiveDate) -> impl Iterator<Item = &Schedule> {
+        self.schedules
+            .iter()
+            .filter(move |s| s.begin_time.date_naive() == date)
+    }
+
+    fn server_now(&self) -> chrono::DateTime<Utc> {
+        Utc::now() + TimeDelta::milliseconds(self.clock_offset_millis)
+    }
+}
+
+impl MockServer {
+    /// Starts a mock server on a random local port with given state.
+    ///
+    /// # Errors
+    ///
+    /// [IO errors](IoError) if the server cannot be started.
+    pub fn start(state: MockState) -> Result<Self, IoError> {
+        let server = Arc::new(Server::http("127.0.0.1:0").map_err(IoError::other)?);
+        let port = server
+            .server_addr()
+            .to_ip()
+            .ok_or_else(|| IoError::other("mock server is not listening on an IP address"))?
+            .port();
+        let api_root = Url::parse(&format!("http://127.0.0.1:{port}/")).map_err(IoError::other)?;
+        let state = Arc::new(Mutex::new(state));
+        let handle = {
+            let server = Arc::clone(&server);
+            let state = Arc::clone(&state);
+            thread::spawn(move || serve(&server, &state))
+        };
+        Ok(Self {
+            server,
+            state,
+            api_root,
+            handle: Some(handle),
+        })
+    }
+
+    /// API root URL of the server, to be passed to [`IClassBuilder::api_root`](super::IClassBuilder::api_root).
+    #[must_use]
+    pub const fn api_root(&self) -> &Url {
+        &self.api_root
+    }
+
+    /// Locks the shared state for inspection or modification.
+    pub fn state(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.server.unblock();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Serves requests until the server is unblocked.
+fn serve(server: &Server, state: &Mutex<MockState>) {
+    for mut request in server.incoming_requests() {
+        let proxied = is_proxied(&request);
+        let (response, date) = {
+            let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+            let response = if !proxied || state.is_proxy_authorized(&request) {
+                Ok(parse_request(&mut request).map(|parsed| state.handle(parsed)))
+            } else {
+                Err(())
+            };
+            (response, state.server_now())
+        };
+        let date = header(
+            "Date",
+            &date.format("%a, %d %b %Y %H:%M:%S GMT").to_string(),
+        );
+        let _ = match response {
+            Ok(Some(body)) => request.respond(
+                Response::from_string(body.to_string())
+                    .with_header(header("Content-Type", "application/json;charset=UTF-8"))
+                    .with_header(date),
+            ),
+            Ok(None) => request.respond(
+                Response::from_string("Not Found")
+                    .with_status_code(404)
+                    .with_header(date),
+            ),
+            Err(()) => request.respond(
+                Response::from_string("Proxy Authentication Required")
+                    .with_status_code(407)
+                    .with_header(header("Proxy-Authenticate", "Basic realm=\"mock\""))
+                    .with_header(date),
+            ),
+        };
+    }
+}
+
+/// Whether given request was sent to the server acting as an HTTP proxy.
+fn is_proxied(request: &Request) -> bool {
+    request.url().starts_with("http://")
+}
+
+/// Parses the endpoint, session id and parameters of given request.
+fn parse_request(request: &mut Request) -> Option<MockRequest> {
+    let url = Url::parse("http://localhost/")
+        .ok()?
+        .join(request.url())
+        .ok()?;
+    let endpoint = match url.path() {
+        "/app/user/login.action" => MockEndpoint::Login,
+        "/app/course/get_base_school_year.action" => MockEndpoint::Semester,
+        "/app/my/get_my_course.action" => MockEndpoint::Courses,
+        "/app/course/get_stu_course_sched.action" => MockEndpoint::DailySchedule,
+        "/app/course/get_stu_course_sched_week.action" => MockEndpoint::WeeklySchedule,
+        "/app/course/stu_scan_sign.action" => MockEndpoint::CheckIn,
+        _ => return None,
+    };
+    let session_id = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("sessionId"))
+        .map(|h| h.value.to_string());
+    let mut body = Vec::new();
+    request.as_reader().read_to_end(&mut body).ok()?;
+    let params = url
+        .query_pairs()
+        .chain(form_urlencoded::parse(&body))
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+    Some(MockRequest {
+        endpoint,
+        session_id,
+        params,
+        proxied: is_proxied(request),
+    })
+}
+
+fn parse_date_param(params: &HashMap<String, String>) -> Result<NaiveDate, MockError> {
+    params
+        .get("dateStr")
+        .and_then(|s| NaiveDate::parse_from_str(s, "%Y%m%d").ok())
+        .ok_or_else(|| error(Some(100), "参数错误"))
+}
+
+/// Encodes given string in standard Base64 with padding.
+fn base64(input: &str) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut output = String::new();
+    for chunk in input.as_bytes().chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                output.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
+
+fn header(field: &str, value: &str) -> Header {
+    Header::from_bytes(field.as_bytes(), value.as_bytes()).expect("header should be valid")
+}
+
+fn error(code: Option<i8>, message: &str) -> MockError {
+    MockError {
+        code,
+        message: message.to_string(),
+    }
+}
+
+fn failure(code: Option<i8>, message: &str) -> Value {
+    let mut envelope = json!({ "STATUS": "1", "ERRMSG": message });
+    if let Some(code) = code {
+        envelope["ERRCODE"] = Value::String(code.to_string());
+    }
+    envelope
+}
+
+fn semester_json(semester: &Semester) -> Value {
+    json!({
+        "code": semester.code,
+        "name": semester.name,
+        "beginDate": semester.begin_date.format("%Y-%m-%d").to_string(),
+        "endDate": semester.end_date.format("%Y-%m-%d").to_string(),
+        "yearStatus": if semester.is_current { "1" } else { "0" },
+    })
+}
+
+fn course_json(course: &Course) -> Value {
+    json!({
+        "courseNum": course.course_id,
+        "courseName": course.course_name,
+        "classroomName": course.classroom_name,
+        "teacherName": course.teacher_name,
+    })
+}
+
+fn schedule_json(schedule: &Schedule, checked_in: bool) -> Value {
+    let mut value = course_json(&schedule.course);
+    value["id"] = Value::String(schedule.id.to_string());
+    value["uuid"] = Value::String(schedule.uuid.to_string());
+    value["signStatus"] = Value::String(if checked_in { "1" } else { "0" }.to_string());
+    value["classBeginTime"] = Value::String(format_datetime_to_str(
+        &schedule.begin_time.with_timezone(&CST_TIMEZONE),
+    ));
+    value["classEndTime"] = Value::String(format_datetime_to_str(
+        &schedule.end_time.with_timezone(&CST_TIMEZONE),
+    ));
+    value
+}
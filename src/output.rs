@@ -0,0 +1,714 @@
+//! Output formats of command results.
+//!
+//! Machine-readable formats follow a versioned schema: JSON output is an object `{"version", "type", "data"}`, and each JSONL line is a record with `version` and `type` fields added. [`SCHEMA_VERSION`] is bumped whenever a field is renamed or removed, or its meaning changes.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::{fmt, str::FromStr};
+use ucas_iclass::{
+    CheckInResult, ClockOffset, Course, Schedule, Semester,
+    util::{CST_TIMEZONE, format_datetime_to_str},
+};
+
+/// Version of the machine-readable output schema.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Output format.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    /// Human-readable text.
+    #[default]
+    Text,
+    /// A single JSON document.
+    Json,
+    /// One JSON record per line.
+    Jsonl,
+    /// Comma-separated values with a header row.
+    Csv,
+    /// Aligned columns with a header row.
+    Table,
+}
+
+impl FromStr for Format {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            "jsonl" => Ok(Self::Jsonl),
+            "csv" => Ok(Self::Csv),
+            "table" => Ok(Self::Table),
+            _ => Err(format!(
+                "unknown format: {s}, expected one of json, jsonl, csv, table or text"
+            )),
+        }
+    }
+}
+
+/// A record that can be printed in every [`Format`].
+pub trait Record: Serialize {
+    /// Type of the record in machine-readable output.
+    const TYPE: &'static str;
+    /// Column names in CSV and table output.
+    const COLUMNS: &'static [&'static str];
+    /// Column values in CSV and table output.
+    fn row(&self) -> Vec<String>;
+}
+
+/// A JSON document wrapping the data with schema information.
+#[derive(Serialize)]
+struct Document<'a, T: ?Sized> {
+    /// Schema version.
+    version: u32,
+    /// Type of the records.
+    #[serde(rename = "type")]
+    type_: &'static str,
+    /// A record or an array of records.
+    data: &'a T,
+}
+
+/// A JSONL line, i.e. a record with schema information.
+#[derive(Serialize)]
+struct Line<'a, T> {
+    /// Schema version.
+    version: u32,
+    /// Type of the record.
+    #[serde(rename = "type")]
+    type_: &'static str,
+    /// The record.
+    #[serde(flatten)]
+    record: &'a T,
+}
+
+/// Prints command results in the chosen format.
+#[derive(Clone, Copy, Debug)]
+pub struct Output {
+    /// The format.
+    format: Format,
+}
+
+impl Output {
+    /// Creates an output in given format.
+    pub const fn new(format: Format) -> Self {
+        Self { format }
+    }
+
+    /// Whether the format is human-readable text.
+    pub fn is_text(self) -> bool {
+        self.format == Format::Text
+    }
+
+    /// Prints a message for humans, to stdout in text format, or to stderr otherwise so that stdout stays machine-readable.
+    pub fn note<D: fmt::Display>(self, message: D) {
+        if self.is_text() {
+            println!("{message}");
+        } else {
+            eprintln!("{message}");
+        }
+    }
+
+    /// Prints a single record, or given text in text format.
+    pub fn record<R: Record, D: fmt::Display>(self, record: &R, text: D) -> Result<()> {
+        match self.format {
+            Format::Text => println!("{text}"),
+            Format::Json => print_document(R::TYPE, record)?,
+            _ => self.rows(std::slice::from_ref(record))?,
+        }
+        Ok(())
+    }
+
+    /// Prints a record of a stream like the status log, as a JSONL line in JSON and JSONL formats, or given text otherwise.
+    pub fn stream<R: Record, D: fmt::Display>(self, record: &R, text: D) -> Result<()> {
+        match self.format {
+            Format::Json | Format::Jsonl => print_line(record)?,
+            _ => println!("{text}"),
+        }
+        Ok(())
+    }
+
+    /// Prints records, or given heading followed by indented records in text format.
+    pub fn records<R: Record + fmt::Display, D: fmt::Display>(
+        self,
+        records: &[R],
+        heading: D,
+    ) -> Result<()> {
+        match self.format {
+            Format::Text => {
+                println!("{heading}");
+                for record in records {
+                    println!("  {record}");
+                }
+            }
+            Format::Json => print_document(R::TYPE, records)?,
+            _ => self.rows(records)?,
+        }
+        Ok(())
+    }
+
+    /// Prints records in JSONL, CSV or table format.
+    fn rows<R: Record>(self, records: &[R]) -> Result<()> {
+        match self.format {
+            Format::Jsonl => {
+                for record in records {
+                    print_line(record)?;
+                }
+            }
+            Format::Csv => {
+                println!("{}", csv_line(R::COLUMNS.iter().copied()));
+                for record in records {
+                    println!("{}", csv_line(record.row().iter().map(String::as_str)));
+                }
+            }
+            Format::Table => print_table(R::COLUMNS, records),
+            Format::Text | Format::Json => unreachable!("handled by callers"),
+        }
+        Ok(())
+    }
+}
+
+/// Prints a JSON document with schema information.
+fn print_document<T: Serialize + ?Sized>(type_: &'static str, data: &T) -> Result<()> {
+    println!("{}", document(type_, data)?);
+    Ok(())
+}
+
+/// Prints a JSONL line with schema information.
+fn print_line<R: Record>(record: &R) -> Result<()> {
+    println!("{}", line(record)?);
+    Ok(())
+}
+
+/// Serializes a JSON document with schema information.
+fn document<T: Serialize + ?Sized>(type_: &'static str, data: &T) -> Result<String> {
+    let document = Document {
+        version: SCHEMA_VERSION,
+        type_,
+        data,
+    };
+    Ok(serde_json::to_string_pretty(&document)?)
+}
+
+/// Serializes a JSONL line with schema information.
+fn line<R: Record>(record: &R) -> Result<String> {
+    let line = Line {
+        version: SCHEMA_VERSION,
+        type_: R::TYPE,
+        record,
+    };
+    Ok(serde_json::to_string(&line)?)
+}
+
+/// Joins fields into a CSV line, quoting fields when needed.
+fn csv_line<'a, I: Iterator<Item = &'a str>>(fields: I) -> String {
+    fields
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Prints records as a table with aligned columns.
+fn print_table<R: Record>(columns: &[&str], records: &[R]) {
+    for line in table(columns, records) {
+        println!("{line}");
+    }
+}
+
+/// Lines of a table of records with aligned columns.
+fn table<R: Record>(columns: &[&str], records: &[R]) -> Vec<String> {
+    let rows: Vec<_> = records.iter().map(Record::row).collect();
+    let mut widths: Vec<_> = columns.iter().map(|column| width(column)).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(self::width(cell));
+        }
+    }
+    let line = |cells: &[&str]| {
+        let cells: Vec<_> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell}{}", " ".repeat(width - self::width(cell))))
+            .collect();
+        cells.join("  ").trim_end().to_string()
+    };
+    let separator: Vec<_> = widths.iter().map(|width| "-".repeat(*width)).collect();
+    let mut lines = vec![line(columns), separator.join("  ")];
+    for row in &rows {
+        lines.push(line(&row.iter().map(String::as_str).collect::<Vec<_>>()));
+    }
+    lines
+}
+
+/// Display width of given text in a terminal, counting East Asian wide characters as two columns.
+fn width(text: &str) -> usize {
+    text.chars()
+        .map(|c| match u32::from(c) {
+            0x1100..=0x115F
+            | 0x2E80..=0xA4CF
+            | 0xAC00..=0xD7A3
+            | 0xF900..=0xFAFF
+            | 0xFE30..=0xFE4F
+            | 0xFF00..=0xFF60
+            | 0xFFE0..=0xFFE6
+            | 0x1F300..=0x1FAFF
+            | 0x20000..=0x3FFFD => 2,
+            _ => 1,
+        })
+        .sum()
+}
+
+impl Record for Semester {
+    const TYPE: &'static str = "semester";
+    const COLUMNS: &'static [&'static str] = &["code", "name", "beginDate", "endDate", "isCurrent"];
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.code.clone(),
+            self.name.clone(),
+            self.begin_date.to_string(),
+            self.end_date.to_string(),
+            self.is_current.to_string(),
+        ]
+    }
+}
+
+impl Record for Course {
+    const TYPE: &'static str = "course";
+    const COLUMNS: &'static [&'static str] =
+        &["courseId", "courseName", "classroomName", "teacherName"];
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.course_id.clone(),
+            self.course_name.clone(),
+            self.classroom_name.clone(),
+            self.teacher_name.clone(),
+        ]
+    }
+}
+
+impl Record for Schedule {
+    const TYPE: &'static str = "schedule";
+    const COLUMNS: &'static [&'static str] = &[
+        "id",
+        "uuid",
+        "checkedIn",
+        "beginTime",
+        "endTime",
+        "courseId",
+        "courseName",
+        "classroomName",
+        "teacherName",
+    ];
+    fn row(&self) -> Vec<String> {
+        let mut row = vec![
+            self.id.to_string(),
+            self.uuid.to_string(),
+            self.checked_in
+                .map(|checked_in| checked_in.to_string())
+                .unwrap_or_default(),
+            format_datetime_to_str(&self.begin_time),
+            format_datetime_to_str(&self.end_time),
+        ];
+        row.extend(self.course.row());
+        row
+    }
+}
+
+/// A schedule with its teaching week.
+#[derive(Serialize)]
+pub struct ScheduleRecord<'a> {
+    /// The schedule.
+    #[serde(flatten)]
+    pub schedule: &'a Schedule,
+    /// Teaching week of the schedule, if the semester is known.
+    pub week: Option<u32>,
+}
+
+impl<'a> ScheduleRecord<'a> {
+    /// Pairs given schedules with their teaching weeks in given semester.
+    pub fn all(schedules: &'a [Schedule], semester: Option<&Semester>) -> Vec<Self> {
+        schedules
+            .iter()
+            .map(|schedule| Self {
+                schedule,
+                week: semester.and_then(|semester| {
+                    semester.week_of(
+                        &schedule
+                            .begin_time
+                            .with_timezone(&CST_TIMEZONE)
+                            .date_naive(),
+                    )
+                }),
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for ScheduleRecord<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.schedule.fmt(f)
+    }
+}
+
+impl Record for ScheduleRecord<'_> {
+    const TYPE: &'static str = "schedule";
+    const COLUMNS: &'static [&'static str] = &[
+        "id",
+        "uuid",
+        "checkedIn",
+        "beginTime",
+        "endTime",
+        "courseId",
+        "courseName",
+        "classroomName",
+        "teacherName",
+        "week",
+    ];
+    fn row(&self) -> Vec<String> {
+        let mut row = self.schedule.row();
+        row.push(self.week.map(|week| week.to_string()).unwrap_or_default());
+        row
+    }
+}
+
+impl Record for ClockOffset {
+    const TYPE: &'static str = "clockOffset";
+    const COLUMNS: &'static [&'static str] = &["offsetMillis", "uncertaintyMillis", "measuredAt"];
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.offset_millis.to_string(),
+            self.uncertainty_millis.to_string(),
+            self.measured_at.to_string(),
+        ]
+    }
+}
+
+/// Result of logging in.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginRecord {
+    /// ID of the user.
+    pub id: String,
+    /// Real name.
+    pub real_name: String,
+    /// Student number.
+    pub student_no: String,
+    /// The file the session is saved to.
+    pub session_file: String,
+}
+
+impl Record for LoginRecord {
+    const TYPE: &'static str = "login";
+    const COLUMNS: &'static [&'static str] = &["id", "realName", "studentNo", "sessionFile"];
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.id.clone(),
+            self.real_name.clone(),
+            self.student_no.clone(),
+            self.session_file.clone(),
+        ]
+    }
+}
+
+/// Result of checking in.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckInRecord {
+    /// Whether the schedule is identified by `id` or `uuid`.
+    pub by: &'static str,
+    /// The schedule id or uuid.
+    pub schedule: String,
+    /// Name of the course, if known.
+    pub course_name: Option<String>,
+    /// The timestamp used in milliseconds.
+    pub timestamp: u128,
+    /// The result.
+    pub result: CheckInResult,
+}
+
+impl Record for CheckInRecord {
+    const TYPE: &'static str = "checkIn";
+    const COLUMNS: &'static [&'static str] = &[
+        "by",
+        "schedule",
+        "courseName",
+        "timestamp",
+        "resultId",
+        "resultStatus",
+    ];
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.by.to_string(),
+            self.schedule.clone(),
+            self.course_name.clone().unwrap_or_default(),
+            self.timestamp.to_string(),
+            self.result.id.clone(),
+            self.result.status.to_string(),
+        ]
+    }
+}
+
+/// A line of the status log.
+#[derive(Serialize)]
+pub struct LogRecord<'a> {
+    /// Time of the line, in "YYYY-MM-DD HH:MM:SS" format in China Standard Time.
+    pub time: &'a str,
+    /// The message.
+    pub message: &'a str,
+}
+
+impl Record for LogRecord<'_> {
+    const TYPE: &'static str = "log";
+    const COLUMNS: &'static [&'static str] = &["time", "message"];
+    fn row(&self) -> Vec<String> {
+        vec![self.time.to_string(), self.message.to_string()]
+    }
+}
+
+/// Attendance of a course.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttendanceRecord {
+    /// Id of the course.
+    pub course_id: String,
+    /// Name of the course.
+    pub course_name: String,
+    /// Number of classes that have ended.
+    pub classes: usize,
+    /// Number of classes checked in.
+    pub attended: usize,
+    /// Ratio of attended classes, if any class has ended.
+    pub attendance_rate: Option<f64>,
+    /// Number of failed check-in attempts recorded for the classes counted.
+    pub failed_attempts: usize,
+    /// Classes not checked in.
+    pub missed: Vec<Schedule>,
+}
+
+impl fmt::Display for AttendanceRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}): {}/{} attended",
+            self.course_name, self.course_id, self.attended, self.classes
+        )?;
+        if let Some(rate) = self.attendance_rate {
+            write!(f, " ({:.1}%)", rate * 100.0)?;
+        }
+        write!(
+            f,
+            ", {} missed, {} failed attempt(s)",
+            self.missed.len(),
+            self.failed_attempts
+        )?;
+        for schedule in &self.missed {
+            write!(f, "\n    Missed: {schedule}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Record for AttendanceRecord {
+    const TYPE: &'static str = "attendance";
+    const COLUMNS: &'static [&'static str] = &[
+        "courseId",
+        "courseName",
+        "classes",
+        "attended",
+        "missed",
+        "attendanceRate",
+        "failedAttempts",
+    ];
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.course_id.clone(),
+            self.course_name.clone(),
+            self.classes.to_string(),
+            self.attended.to_string(),
+            self.missed.len().to_string(),
+            self.attendance_rate
+                .map(|rate| format!("{rate:.4}"))
+                .unwrap_or_default(),
+            self.failed_attempts.to_string(),
+        ]
+    }
+}
+
+/// A setting of a profile in the config file.
+#[derive(Serialize)]
+pub struct ConfigRecord {
+    /// Name of the profile.
+    pub profile: String,
+    /// The key.
+    pub key: &'static str,
+    /// The value.
+    pub value: String,
+}
+
+impl fmt::Display for ConfigRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} = {}", self.key, self.value)
+    }
+}
+
+impl Record for ConfigRecord {
+    const TYPE: &'static str = "config";
+    const COLUMNS: &'static [&'static str] = &["profile", "key", "value"];
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.profile.clone(),
+            self.key.to_string(),
+            self.value.clone(),
+        ]
+    }
+}
+
+/// A stored account.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountRecord {
+    /// Student number.
+    pub student_no: String,
+    /// Real name.
+    pub real_name: String,
+    /// ID of the user.
+    pub id: String,
+    /// Whether it is the default account.
+    pub default: bool,
+    /// The session file of the account.
+    pub session_file: String,
+}
+
+impl fmt::Display for AccountRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} (id={})", self.student_no, self.real_name, self.id)?;
+        if self.default {
+            f.write_str(" [default]")?;
+        }
+        Ok(())
+    }
+}
+
+impl Record for AccountRecord {
+    const TYPE: &'static str = "account";
+    const COLUMNS: &'static [&'static str] =
+        &["studentNo", "realName", "id", "default", "sessionFile"];
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.student_no.clone(),
+            self.real_name.clone(),
+            self.id.clone(),
+            self.default.to_string(),
+            self.session_file.clone(),
+        ]
+    }
+}
+
+/// Outcome of running a command for one of the stored accounts.
+pub struct AccountResultRecord {
+    /// Student number.
+    pub student_no: String,
+    /// Real name.
+    pub real_name: String,
+    /// Exit code the command would have exited with for this account alone.
+    pub exit_code: u8,
+    /// The error, if the command failed.
+    pub error: Option<String>,
+}
+
+impl fmt::Display for AccountResultRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}: ", self.student_no, self.real_name)?;
+        match &self.error {
+            Some(error) => write!(f, "failed (exit code {}): {error}", self.exit_code),
+            None => f.write_str("ok"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConfigRecord, SCHEMA_VERSION, csv_line, document, line, table, width};
+    use serde_json::{Value, json};
+
+    fn record(key: &'static str, value: &str) -> ConfigRecord {
+        ConfigRecord {
+            profile: "default".to_string(),
+            key,
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn csv_quoting() {
+        assert_eq!(csv_line(["a", "b c", ""].into_iter()), "a,b c,");
+        assert_eq!(csv_line(["a,b", "x"].into_iter()), "\"a,b\",x");
+        assert_eq!(
+            csv_line(["say \"hi\"", "x"].into_iter()),
+            "\"say \"\"hi\"\"\",x"
+        );
+        assert_eq!(
+            csv_line(["line\nbreak", "cr\r"].into_iter()),
+            "\"line\nbreak\",\"cr\r\""
+        );
+    }
+
+    #[test]
+    fn json_document() {
+        let records = [record("format", "json")];
+        let document: Value = serde_json::from_str(&document("config", &records).unwrap()).unwrap();
+        assert_eq!(
+            document,
+            json!({
+                "version": SCHEMA_VERSION,
+                "type": "config",
+                "data": [{"profile": "default", "key": "format", "value": "json"}],
+            })
+        );
+    }
+
+    #[test]
+    fn jsonl_line() {
+        let line = line(&record("format", "jsonl")).unwrap();
+        assert!(!line.contains('\n'));
+        let line: Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(
+            line,
+            json!({
+                "version": SCHEMA_VERSION,
+                "type": "config",
+                "profile": "default",
+                "key": "format",
+                "value": "jsonl",
+            })
+        );
+    }
+
+    #[test]
+    fn table_widths() {
+        assert_eq!(width("abc"), 3);
+        assert_eq!(width("张三"), 4);
+        assert_eq!(width("数学A"), 5);
+        assert_eq!(width("ｆｕｌｌ"), 8);
+
+        let records = [record("name", "张三"), record("course", "Math")];
+        assert_eq!(
+            table(&["profile", "key", "value"], &records),
+            [
+                "profile  key     value",
+                "-------  ------  -----",
+                "default  name    张三",
+                "default  course  Math",
+            ]
+        );
+        let records = [record("name", "高等数学")];
+        assert_eq!(
+            table(&["profile", "key", "value"], &records)[1..],
+            ["-------  ----  --------", "default  name  高等数学"]
+        );
+    }
+}
@@ -0,0 +1,220 @@
+//! Reading the password to log in with, without exposing it on the command line.
+
+use super::cli::PASSWORD_ENV;
+use anyhow::{Context, Result, bail};
+use std::{
+    env,
+    io::{self, BufRead},
+    process::{Command, Stdio},
+};
+
+/// Where the password may come from, injectable for testing.
+pub struct Sources {
+    /// Reads a line from stdin.
+    pub stdin: fn() -> io::Result<String>,
+    /// Runs a password command, returning its whole output.
+    pub run: fn(&str) -> Result<String>,
+    /// Looks up an environment variable.
+    pub env: fn(&str) -> Option<String>,
+    /// Prompts for the password on the terminal.
+    pub prompt: fn() -> io::Result<String>,
+    /// Prints a warning.
+    pub warn: fn(&str),
+}
+
+impl Sources {
+    /// The actual stdin, shell, environment, terminal and stderr.
+    pub const SYSTEM: Self = Self {
+        stdin: || {
+            let mut line = String::new();
+            io::stdin().lock().read_line(&mut line)?;
+            Ok(line)
+        },
+        run,
+        env: |name| env::var(name).ok(),
+        prompt: || rpassword::prompt_password("Password: "),
+        warn: |warning| eprintln!("Warning: {warning}"),
+    };
+}
+
+/// Resolves the password to log in with from the [system sources](Sources::SYSTEM), like [`resolve_from`].
+pub fn resolve(positional: Option<String>, stdin: bool, command: Option<&str>) -> Result<String> {
+    resolve_from(positional, stdin, command, &Sources::SYSTEM)
+}
+
+/// Resolves the password to log in with: from stdin with `--password-stdin`, or else from the output of `--password-command`, or else the positional argument with a warning, or else the [`PASSWORD_ENV`] environment variable, or else a hidden prompt on the terminal. Only the first line of stdin or the command output is taken, without the line ending.
+pub fn resolve_from(
+    positional: Option<String>,
+    stdin: bool,
+    command: Option<&str>,
+    sources: &Sources,
+) -> Result<String> {
+    let password = match (positional, stdin, command) {
+        (None, true, None) => first_line(&(sources.stdin)()?),
+        (None, false, Some(command)) => first_line(&(sources.run)(command)?),
+        (Some(password), false, None) => {
+            (sources.warn)(&format!(
+                "the password given as an argument is exposed in shell history and process lists, use the prompt, --password-stdin, --password-command or {PASSWORD_ENV} instead"
+            ));
+            password
+        }
+        (None, false, None) => match (sources.env)(PASSWORD_ENV) {
+            Some(password) => password,
+            None => (sources.prompt)().with_context(|| {
+                format!(
+                    "Cannot prompt for the password, use --password-stdin, --password-command or {PASSWORD_ENV}"
+                )
+            })?,
+        },
+        _ => bail!(
+            "Give the password by only one of the argument, --password-stdin and --password-command"
+        ),
+    };
+    if password.is_empty() {
+        bail!("The password is empty");
+    }
+    Ok(password)
+}
+
+/// Runs given shell command, like `pass show iclass`, returning its output. Its stdin and stderr are inherited, so that it can ask for a passphrase.
+fn run(command: &str) -> Result<String> {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let output = Command::new(shell)
+        .args([flag, command])
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .with_context(|| format!("Cannot run password command: {command}"))?;
+    if !output.status.success() {
+        bail!("Password command failed with {}: {command}", output.status);
+    }
+    String::from_utf8(output.stdout).context("Password command output is not UTF-8")
+}
+
+/// The first line of given text, without the line ending.
+fn first_line(text: &str) -> String {
+    text.lines().next().unwrap_or_default().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PASSWORD_ENV, Sources, resolve_from};
+    use anyhow::{Result, bail};
+    use std::{cell::Cell, io};
+
+    /// Sources that each yield a password naming themselves, with the command output echoing the command.
+    const SOURCES: Sources = Sources {
+        stdin: || Ok("stdin\n".to_string()),
+        run: |command| Ok(format!("{command}\r\nsecond line\n")),
+        env: |name| (name == PASSWORD_ENV).then(|| "env".to_string()),
+        prompt: || Ok("prompt".to_string()),
+        warn: |_| {},
+    };
+
+    fn resolve(
+        positional: Option<&str>,
+        stdin: bool,
+        command: Option<&str>,
+        sources: &Sources,
+    ) -> Result<String> {
+        resolve_from(positional.map(str::to_string), stdin, command, sources)
+    }
+
+    #[test]
+    fn order() {
+        assert_eq!(resolve(None, true, None, &SOURCES).unwrap(), "stdin");
+        assert_eq!(
+            resolve(None, false, Some("command"), &SOURCES).unwrap(),
+            "command"
+        );
+        assert_eq!(
+            resolve(Some("argument"), false, None, &SOURCES).unwrap(),
+            "argument"
+        );
+        assert_eq!(resolve(None, false, None, &SOURCES).unwrap(), "env");
+        let no_env = Sources {
+            env: |_| None,
+            ..SOURCES
+        };
+        assert_eq!(resolve(None, false, None, &no_env).unwrap(), "prompt");
+        let no_terminal = Sources {
+            prompt: || Err(io::Error::other("no terminal")),
+            ..no_env
+        };
+        let error = resolve(None, false, None, &no_terminal).unwrap_err();
+        assert!(error.to_string().starts_with("Cannot prompt"));
+    }
+
+    #[test]
+    fn conflicting_sources() {
+        for (positional, stdin, command) in [
+            (Some("argument"), true, None),
+            (Some("argument"), false, Some("command")),
+            (None, true, Some("command")),
+        ] {
+            let error = resolve(positional, stdin, command, &SOURCES).unwrap_err();
+            assert!(
+                error
+                    .to_string()
+                    .starts_with("Give the password by only one")
+            );
+        }
+    }
+
+    #[test]
+    fn argument_warns() {
+        thread_local! {
+            static WARNINGS: Cell<usize> = const { Cell::new(0) };
+        }
+        let counting = Sources {
+            warn: |_| WARNINGS.set(WARNINGS.get() + 1),
+            ..SOURCES
+        };
+        resolve(None, false, None, &counting).unwrap();
+        assert_eq!(WARNINGS.get(), 0);
+        resolve(Some("argument"), false, None, &counting).unwrap();
+        assert_eq!(WARNINGS.get(), 1);
+    }
+
+    #[test]
+    fn failing_or_empty_command() {
+        let failing = Sources {
+            run: |command| bail!("Password command failed with exit status: 1: {command}"),
+            ..SOURCES
+        };
+        let error = resolve(None, false, Some("false"), &failing).unwrap_err();
+        assert!(error.to_string().starts_with("Password command failed"));
+
+        let empty = Sources {
+            run: |_| Ok("\n".to_string()),
+            stdin: || Ok(String::new()),
+            env: |_| Some(String::new()),
+            ..SOURCES
+        };
+        for (stdin, command) in [(false, Some("true")), (true, None), (false, None)] {
+            let error = resolve(None, stdin, command, &empty).unwrap_err();
+            assert_eq!(error.to_string(), "The password is empty");
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn shell_command() {
+        let sources = Sources {
+            run: super::run,
+            ..SOURCES
+        };
+        assert_eq!(
+            resolve(None, false, Some("printf 'secret\\n'"), &sources).unwrap(),
+            "secret"
+        );
+        let error = resolve(None, false, Some("exit 3"), &sources).unwrap_err();
+        assert!(error.to_string().starts_with("Password command failed"));
+        let error = resolve(None, false, Some("true"), &sources).unwrap_err();
+        assert_eq!(error.to_string(), "The password is empty");
+    }
+}
@@ -0,0 +1,86 @@
+//! Pick the schedules currently eligible for check-in.
+
+use super::{IClass, IClassError, Schedule, transport::Transport};
+use chrono::{DateTime, Duration, FixedOffset};
+
+/// Policy of which schedules are eligible for check-in at a given time, used by [`IClass::current_schedules`].
+///
+/// Eligible schedules are ranked with those not checked in first, then by id in numeric order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CheckInPolicy {
+    /// How long before a schedule begins it becomes eligible.
+    pub lead_time: Duration,
+    /// How long after a schedule begins it stays eligible, or until it ends if `None`.
+    pub grace_period: Option<Duration>,
+    /// Whether schedules already checked in are eligible.
+    pub include_checked_in: bool,
+}
+
+impl Default for CheckInPolicy {
+    /// Eligible from 30 minutes before a schedule begins until it ends, if not checked in yet.
+    fn default() -> Self {
+        Self {
+            lead_time: Duration::minutes(30),
+            grace_period: None,
+            include_checked_in: false,
+        }
+    }
+}
+
+impl CheckInPolicy {
+    /// When given schedule becomes eligible.
+    #[must_use]
+    pub fn window_open(&self, schedule: &Schedule) -> DateTime<FixedOffset> {
+        schedule.begin_time - self.lead_time
+    }
+
+    /// When given schedule stops being eligible.
+    #[must_use]
+    pub fn window_close(&self, schedule: &Schedule) -> DateTime<FixedOffset> {
+        self.grace_period
+            .map_or(schedule.end_time, |grace| schedule.begin_time + grace)
+    }
+
+    /// Whether given schedule is eligible at given time.
+    #[must_use]
+    pub fn is_eligible(&self, schedule: &Schedule, now: DateTime<FixedOffset>) -> bool {
+        (self.include_checked_in || schedule.checked_in != Some(true))
+            && self.window_open(schedule) <= now
+            && now <= self.window_close(schedule)
+    }
+
+    /// Keeps the schedules eligible at given time, ranked.
+    #[must_use]
+    pub fn rank(&self, schedules: Vec<Schedule>, now: DateTime<FixedOffset>) -> Vec<Schedule> {
+        let mut eligible: Vec<_> = schedules
+            .into_iter()
+            .filter(|schedule| self.is_eligible(schedule, now))
+            .collect();
+        eligible.sort_by(|a, b| {
+            (a.checked_in == Some(true))
+                .cmp(&(b.checked_in == Some(true)))
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        eligible
+    }
+}
+
+#[allow(
+    clippy::future_not_send,
+    reason = "`Send` whenever the transport is, see `Transport`"
+)]
+impl<T: Transport> IClass<T> {
+    /// Queries today's schedule, and returns the schedules eligible for check-in under given policy, ranked. The server clock is used for the current time, see [`server_now`](Self::server_now).
+    ///
+    /// # Errors
+    ///
+    /// See [`IClassError`].
+    pub async fn current_schedules(
+        &self,
+        policy: &CheckInPolicy,
+    ) -> Result<Vec<Schedule>, IClassError> {
+        let now = self.server_now();
+        let schedules = self.query_daily_schedule(&now.date_naive()).await?;
+        Ok(policy.rank(schedules, now))
+    }
+}
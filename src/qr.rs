@@ -0,0 +1,209 @@
+//! Parse check-in QR code payloads, and decode them from images with the `qr-image` feature.
+
+use super::{CheckInTarget, IClassError, ScheduleId, ScheduleUuid};
+use std::{ops::Range, str::FromStr};
+use url::{Url, form_urlencoded};
+
+/// Plausible timestamps in seconds since UNIX epoch, from 2001-09-09 to 2286-11-20.
+const TIMESTAMP_SECS: Range<u128> = 1_000_000_000..10_000_000_000;
+/// Plausible timestamps in milliseconds since UNIX epoch, over the same period.
+const TIMESTAMP_MILLIS: Range<u128> = 1_000_000_000_000..10_000_000_000_000;
+
+/// A scanned check-in QR code payload.
+///
+/// Accepted formats are the check-in URL encoded in the QR code, like `https://iclass.ucas.edu.cn:8181/app/course/stu_scan_sign.action?timeTableId=<uuid>&timestamp=<timestamp>`, with the parameters either in its query or after a `?` in its fragment, and the bare query string. The host and path are not checked, so that QR codes of other schools running iClass work as well.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QrPayload {
+    /// The schedule to check in.
+    pub target: CheckInTarget,
+    /// Timestamp in milliseconds embedded in the QR code, if any. Timestamps in seconds are converted, and those implausible in either unit are rejected.
+    pub timestamp: Option<u128>,
+}
+
+impl FromStr for QrPayload {
+    type Err = IClassError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let invalid = || IClassError::InvalidQrPayload(s.to_string());
+        let query = match Url::parse(s) {
+            Ok(url) => url
+                .query()
+                .filter(|query| has_target(query))
+                .or_else(|| {
+                    url.fragment()
+                        .and_then(|fragment| fragment.split_once('?'))
+                        .map(|(_, query)| query)
+                })
+                .map(str::to_string)
+                .ok_or_else(invalid)?,
+            Err(_) => s.trim_start_matches('?').to_string(),
+        };
+        let mut uuid = None;
+        let mut id = None;
+        let mut timestamp = None;
+        for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+            match key.as_ref() {
+                "timeTableId" => uuid = Some(value.into_owned()),
+                "courseSchedId" => id = Some(value.into_owned()),
+                "timestamp" => {
+                    let value: u128 = value.parse().map_err(|_| invalid())?;
+                    timestamp = Some(if TIMESTAMP_MILLIS.contains(&value) {
+                        value
+                    } else if TIMESTAMP_SECS.contains(&value) {
+                        value * 1000
+                    } else {
+                        return Err(invalid());
+                    });
+                }
+                _ => {}
+            }
+        }
+        let target = match (uuid, id) {
+            (Some(uuid), _) if !uuid.is_empty() => {
+                CheckInTarget::Uuid(uuid.parse::<ScheduleUuid>().map_err(|_| invalid())?)
+            }
+            (_, Some(id)) if !id.is_empty() => {
+                CheckInTarget::Id(id.parse::<ScheduleId>().map_err(|_| invalid())?)
+            }
+            _ => return Err(invalid()),
+        };
+        Ok(Self { target, timestamp })
+    }
+}
+
+#[cfg(feature = "qr-image")]
+impl QrPayload {
+    /// Decodes the iClass check-in QR code in given PNG or JPEG image, like a screenshot or a photo taken at a slight angle.
+    ///
+    /// # Errors
+    ///
+    /// [`IClassError::QrImageError`] if the image cannot be read, or contains no iClass check-in QR code.
+    pub fn from_image(image: &[u8]) -> Result<Self, IClassError> {
+        let gray = image::load_from_memory(image)
+            .map_err(|e| IClassError::QrImageError(format!("cannot read image: {e}")))?
+            .to_luma8();
+        let mut texts = decode_grids(gray.clone());
+        if texts.is_empty() {
+            // Small codes with modules of a pixel or two are only found when smoothed out
+            let (width, height) = gray.dimensions();
+            texts = decode_grids(image::imageops::resize(
+                &gray,
+                width * 2,
+                height * 2,
+                image::imageops::FilterType::Triangle,
+            ));
+        }
+        let mut others: Vec<String> = Vec::new();
+        for text in texts {
+            if let Ok(payload) = text.parse() {
+                return Ok(payload);
+            }
+            if !others.contains(&text) {
+                others.push(text);
+            }
+        }
+        let message = if others.is_empty() {
+            "no QR code found".to_string()
+        } else {
+            format!(
+                "no iClass check-in QR code found, only: {}",
+                others.join(", ")
+            )
+        };
+        Err(IClassError::QrImageError(message))
+    }
+}
+
+/// Detects and decodes the QR codes in given grayscale image, skipping unreadable ones.
+#[cfg(feature = "qr-image")]
+fn decode_grids(gray: image::GrayImage) -> Vec<String> {
+    rqrr::PreparedImage::prepare(gray)
+        .detect_grids()
+        .into_iter()
+        .filter_map(|grid| grid.decode().ok().map(|(_, text)| text))
+        .collect()
+}
+
+/// Whether given query string contains a schedule uuid or id.
+fn has_target(query: &str) -> bool {
+    form_urlencoded::parse(query.as_bytes())
+        .any(|(key, _)| key == "timeTableId" || key == "courseSchedId")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CheckInTarget, IClassError, QrPayload};
+
+    const UUID: &str = "0123456789abcdef0123456789abcdef";
+
+    fn parse(s: &str) -> Result<QrPayload, IClassError> {
+        s.parse()
+    }
+
+    fn uuid() -> CheckInTarget {
+        CheckInTarget::Uuid(UUID.parse().unwrap())
+    }
+
+    #[test]
+    fn valid_payloads() {
+        let payload = parse(&format!(
+            "https://iclass.ucas.edu.cn:8181/app/course/stu_scan_sign.action?timeTableId={UUID}&timestamp=1760000000000"
+        ))
+        .unwrap();
+        assert_eq!(payload.target, uuid());
+        assert_eq!(payload.timestamp, Some(1_760_000_000_000));
+
+        let payload = parse(&format!(
+            "https://iclass.ucas.edu.cn:8181/#/sign?timeTableId={UUID}"
+        ))
+        .unwrap();
+        assert_eq!(payload.target, uuid());
+        assert_eq!(payload.timestamp, None);
+
+        let payload = parse(" ?courseSchedId=123456&timestamp=1760000000000\n").unwrap();
+        assert_eq!(payload.target, CheckInTarget::Id("123456".parse().unwrap()));
+
+        // An empty uuid falls back to the id
+        let payload = parse("timeTableId=&courseSchedId=42").unwrap();
+        assert_eq!(payload.target, CheckInTarget::Id("42".parse().unwrap()));
+    }
+
+    #[test]
+    fn malformed_payloads() {
+        for payload in [
+            "",
+            "hello world",
+            "https://iclass.ucas.edu.cn:8181/app/course/stu_scan_sign.action",
+            "timeTableId=abc",
+            "timeTableId=0123456789abcdef0123456789abcdeg",
+            "courseSchedId=12a",
+            "courseSchedId=-1",
+            "courseSchedId=1&timestamp=soon",
+        ] {
+            assert!(
+                matches!(parse(payload), Err(IClassError::InvalidQrPayload(_))),
+                "{payload:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn timestamps_in_seconds_or_millis() {
+        let timestamp = |timestamp: &str| {
+            parse(&format!("timeTableId={UUID}&timestamp={timestamp}")).map(|p| p.timestamp)
+        };
+        assert_eq!(timestamp("1760000000").unwrap(), Some(1_760_000_000_000));
+        assert_eq!(timestamp("1760000000123").unwrap(), Some(1_760_000_000_123));
+        assert_eq!(timestamp("1000000000").unwrap(), Some(1_000_000_000_000));
+        assert_eq!(timestamp("9999999999999").unwrap(), Some(9_999_999_999_999));
+        for implausible in ["0", "1", "999999999", "10000000000", "10000000000000"] {
+            assert!(
+                matches!(
+                    timestamp(implausible),
+                    Err(IClassError::InvalidQrPayload(_))
+                ),
+                "{implausible} should be rejected"
+            );
+        }
+    }
+}
@@ -1,13 +1,23 @@
 //! Query selected courses.
 
-use chrono::{DateTime, FixedOffset, NaiveDate};
-use std::fmt;
+use chrono::{DateTime, Datelike, Days, FixedOffset, NaiveDate};
+use futures_util::{Stream, StreamExt, TryStreamExt, future, stream};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
 
-use super::{IClass, IClassError, Response};
-use serde::Deserialize;
+use super::{
+    IClass, IClassError, ScheduleId, ScheduleUuid,
+    transport::{Transport, TransportRequest},
+};
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of schedule requests in flight when querying a range of dates, or check-in status of a week.
+pub const MAX_CONCURRENT_REQUESTS: usize = 4;
 
 /// A semester.
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Semester {
     /// Semester code.
@@ -15,27 +25,33 @@ pub struct Semester {
     /// Semester name.
     pub name: String,
     /// Semester begin date.
-    #[serde(deserialize_with = "super::util::deserialize_str_to_date_hyphen")]
+    #[serde(
+        serialize_with = "super::util::serialize_date_to_str_hyphen",
+        deserialize_with = "super::util::deserialize_str_to_date_hyphen"
+    )]
     pub begin_date: NaiveDate,
     /// Semester end date.
-    #[serde(deserialize_with = "super::util::deserialize_str_to_date_hyphen")]
+    #[serde(
+        serialize_with = "super::util::serialize_date_to_str_hyphen",
+        deserialize_with = "super::util::deserialize_str_to_date_hyphen"
+    )]
     pub end_date: NaiveDate,
     /// Whether it is the current semester.
     #[serde(
-        rename = "yearStatus",
+        rename(deserialize = "yearStatus"),
         deserialize_with = "super::util::deserialize_str_to_bool"
     )]
     pub is_current: bool,
 }
 
 /// A course.
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Course {
     // /// Course ID in iClass system.
     // pub id: String,
     /// Course ID as we all know.
-    #[serde(rename = "courseNum")]
+    #[serde(rename(deserialize = "courseNum"))]
     pub course_id: String,
     /// Course name. There may be courses with the same name.
     pub course_name: String,
@@ -46,51 +62,60 @@ pub struct Course {
 }
 
 /// A daily schedule.
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DailySchedule {
     /// Date of this schedule.
     #[serde(
-        rename = "dateStr",
+        rename(deserialize = "dateStr"),
+        serialize_with = "super::util::serialize_date_to_str",
         deserialize_with = "super::util::deserialize_str_to_date"
     )]
     pub date: NaiveDate,
     /// Schedules in this day.
-    #[serde(rename = "schedData")]
+    #[serde(rename(deserialize = "schedData"))]
     pub schedules: Vec<Schedule>,
 }
 
 /// A single schedule entry.
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Schedule {
     /// The course scheduled.
     #[serde(flatten)]
     pub course: Course,
     /// Id of this schedule.
-    pub id: String,
+    pub id: ScheduleId,
     /// Unique id of this schedule.
-    pub uuid: String,
-    /// Check in status. Only work in [`query_daily_schedule`](IClass::query_daily_schedule), and does not work in [`query_weekly_schedule`](IClass::query_weekly_schedule) or when wrapped in [`DailySchedule`](DailySchedule).
+    pub uuid: ScheduleUuid,
+    /// Check in status, `None` if unknown. Only known in [`query_daily_schedule`](IClass::query_daily_schedule) and [`query_weekly_schedule_with_status`](IClass::query_weekly_schedule_with_status), since the API does not report it in weekly schedules.
     #[serde(
-        rename = "signStatus",
-        deserialize_with = "super::util::deserialize_str_to_bool"
+        default,
+        rename(deserialize = "signStatus"),
+        deserialize_with = "super::util::deserialize_opt_str_to_bool"
     )]
-    pub checked_in: bool,
+    pub checked_in: Option<bool>,
     /// Begin time.
     #[serde(
-        rename = "classBeginTime",
+        rename(deserialize = "classBeginTime"),
+        serialize_with = "super::util::serialize_datetime_to_str",
         deserialize_with = "super::util::deserialize_str_to_datetime"
     )]
     pub begin_time: DateTime<FixedOffset>,
     /// End time.
     #[serde(
-        rename = "classEndTime",
+        rename(deserialize = "classEndTime"),
+        serialize_with = "super::util::serialize_datetime_to_str",
         deserialize_with = "super::util::deserialize_str_to_datetime"
     )]
     pub end_time: DateTime<FixedOffset>,
 }
 
-impl IClass {
+#[allow(
+    clippy::future_not_send,
+    reason = "`Send` whenever the transport is, see `Transport`"
+)]
+impl<T: Transport> IClass<T> {
     /// Queries current semester.
     ///
     /// # Errors
@@ -100,15 +125,11 @@ impl IClass {
         let url = self
             .api_root
             .join("app/course/get_base_school_year.action")?;
-        let response: Response<Vec<Semester>> = self
-            .client
-            .post(url)?
-            .form(&[("userId", &self.get_user_session()?.id)])?
-            .send()
-            .await?
-            .json()
+        let semesters = self
+            .with_session(|user_session| {
+                TransportRequest::post(url.clone()).form(&[("userId", &user_session.id)])
+            })
             .await?;
-        let semesters = response.into_result()?;
         Ok(semesters)
     }
 
@@ -120,18 +141,14 @@ impl IClass {
     ///
     /// See [`IClassError`].
     pub async fn query_courses(&self) -> Result<Vec<Course>, IClassError> {
-        let user_session = self.get_user_session()?;
         let url = self.api_root.join("app/my/get_my_course.action")?;
-        let response: Response<Vec<Course>> = self
-            .client
-            .post(url)?
-            .header("sessionId", &user_session.session_id)?
-            .form(&[("id", &user_session.id)])?
-            .send()
-            .await?
-            .json()
+        let courses = self
+            .with_session(|user_session| {
+                TransportRequest::post(url.clone())
+                    .header("sessionId", &user_session.session_id)
+                    .form(&[("id", &user_session.id)])
+            })
             .await?;
-        let courses = response.into_result()?;
 
         Ok(courses)
     }
@@ -149,26 +166,22 @@ impl IClass {
         &self,
         date: &NaiveDate,
     ) -> Result<Vec<Schedule>, IClassError> {
-        let user_session = self.get_user_session()?;
         let url = self
             .api_root
             .join("app/course/get_stu_course_sched.action")?;
         let date_str = super::util::format_date_to_str(date);
-        let response: Response<Vec<Schedule>> = self
-            .client
-            .post(url)?
-            .header("sessionId", &user_session.session_id)?
-            .form(&[("id", &user_session.id), ("dateStr", &date_str)])?
-            .send()
-            .await?
-            .json()
+        let daily_schedule = self
+            .with_session(|user_session| {
+                TransportRequest::post(url.clone())
+                    .header("sessionId", &user_session.session_id)
+                    .form(&[("id", &user_session.id), ("dateStr", &date_str)])
+            })
             .await?;
-        let daily_schedule = response.into_result()?;
 
         Ok(daily_schedule)
     }
 
-    /// Queries weekly schedule.
+    /// Queries weekly schedule. Check-in status is unknown, see [`query_weekly_schedule_with_status`](Self::query_weekly_schedule_with_status) to fill it in.
     ///
     /// # Arguments
     ///
@@ -181,24 +194,184 @@ impl IClass {
         &self,
         date: &NaiveDate,
     ) -> Result<Vec<DailySchedule>, IClassError> {
-        let user_session = self.get_user_session()?;
         let url = self
             .api_root
             .join("app/course/get_stu_course_sched_week.action")?;
         let date_str = super::util::format_date_to_str(date);
-        let response: Response<Vec<DailySchedule>> = self
-            .client
-            .post(url)?
-            .header("sessionId", &user_session.session_id)?
-            .form(&[("id", &user_session.id), ("dateStr", &date_str)])?
-            .send()
-            .await?
-            .json()
+        let mut week_schedule: Vec<DailySchedule> = self
+            .with_session(|user_session| {
+                TransportRequest::post(url.clone())
+                    .header("sessionId", &user_session.session_id)
+                    .form(&[("id", &user_session.id), ("dateStr", &date_str)])
+            })
             .await?;
-        let week_schedule = response.into_result()?;
+        // Sign status in weekly schedules is always "0", so do not trust it
+        for schedule in week_schedule
+            .iter_mut()
+            .flat_map(|daily_schedule| &mut daily_schedule.schedules)
+        {
+            schedule.checked_in = None;
+        }
 
         Ok(week_schedule)
     }
+
+    /// Queries weekly schedule with check-in status, by also querying the daily schedule of each day with classes. See [`fill_check_in_status`](Self::fill_check_in_status).
+    ///
+    /// # Arguments
+    ///
+    /// - `date` - A date string in "YYYYMMDD" format within the week to query, like "20251013".
+    ///
+    /// # Errors
+    ///
+    /// See [`IClassError`].
+    pub async fn query_weekly_schedule_with_status(
+        &self,
+        date: &NaiveDate,
+    ) -> Result<Vec<DailySchedule>, IClassError> {
+        let mut week_schedule = self.query_weekly_schedule(date).await?;
+        self.fill_check_in_status(&mut week_schedule).await?;
+        Ok(week_schedule)
+    }
+
+    /// Fills in unknown check-in status of given daily schedules, by querying the daily schedule of each day with such classes and merging by uuid.
+    ///
+    /// Daily schedules are requested with at most [`MAX_CONCURRENT_REQUESTS`] requests in flight. Status of classes missing from the daily schedules stays unknown.
+    ///
+    /// # Errors
+    ///
+    /// See [`IClassError`].
+    pub async fn fill_check_in_status(
+        &self,
+        daily_schedules: &mut [DailySchedule],
+    ) -> Result<(), IClassError> {
+        let dates: Vec<_> = daily_schedules
+            .iter()
+            .filter(|daily_schedule| {
+                daily_schedule
+                    .schedules
+                    .iter()
+                    .any(|schedule| schedule.checked_in.is_none())
+            })
+            .map(|daily_schedule| daily_schedule.date)
+            .collect();
+        let daily_results: Vec<Vec<Schedule>> = stream::iter(dates)
+            .map(async |date| self.query_daily_schedule(&date).await)
+            .buffer_unordered(MAX_CONCURRENT_REQUESTS)
+            .try_collect()
+            .await?;
+        let status: HashMap<_, _> = daily_results
+            .into_iter()
+            .flatten()
+            .map(|schedule| (schedule.uuid, schedule.checked_in))
+            .collect();
+        for schedule in daily_schedules
+            .iter_mut()
+            .flat_map(|daily_schedule| &mut daily_schedule.schedules)
+            .filter(|schedule| schedule.checked_in.is_none())
+        {
+            if let Some(checked_in) = status.get(&schedule.uuid) {
+                schedule.checked_in = *checked_in;
+            }
+        }
+        Ok(())
+    }
+
+    /// Queries schedules from `start` to `end` (both inclusive) as a stream of daily schedules in date order, so that callers can process them week by week.
+    ///
+    /// Weekly schedules are requested with at most [`MAX_CONCURRENT_REQUESTS`] requests in flight, and schedules appearing more than once are yielded only once, judging by their uuid. Days outside the range are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Each item is an error if the corresponding weekly request fails. See [`IClassError`].
+    pub fn query_schedule_range(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> impl Stream<Item = Result<DailySchedule, IClassError>> {
+        // Query each week by a date in it, plus the end date in case it falls in the week after the last one
+        let mut dates: Vec<_> = start.iter_weeks().take_while(|date| *date <= end).collect();
+        if dates
+            .last()
+            .is_some_and(|last| last.iso_week() != end.iso_week())
+        {
+            dates.push(end);
+        }
+        let mut last_date = None;
+        let mut seen = HashSet::new();
+        stream::iter(dates)
+            .map(async |date| self.query_weekly_schedule(&date).await)
+            .buffered(MAX_CONCURRENT_REQUESTS)
+            .map_ok(|weekly_schedule| stream::iter(weekly_schedule).map(Ok))
+            .try_flatten()
+            .try_filter_map(move |mut daily_schedule| {
+                let date = daily_schedule.date;
+                let in_order = last_date.is_none_or(|last| date > last);
+                let daily_schedule = if in_order && start <= date && date <= end {
+                    last_date = Some(date);
+                    daily_schedule
+                        .schedules
+                        .retain(|schedule| seen.insert(schedule.uuid.clone()));
+                    Some(daily_schedule)
+                } else {
+                    None
+                };
+                future::ready(Ok(daily_schedule))
+            })
+    }
+
+    /// Queries schedules of given semester, from its begin date to its end date. See [`query_schedule_range`](Self::query_schedule_range).
+    ///
+    /// # Errors
+    ///
+    /// See [`query_schedule_range`](Self::query_schedule_range).
+    pub fn query_semester_schedule(
+        &self,
+        semester: &Semester,
+    ) -> impl Stream<Item = Result<DailySchedule, IClassError>> {
+        self.query_schedule_range(semester.begin_date, semester.end_date)
+    }
+}
+
+impl Semester {
+    /// Whether given date is within this semester.
+    #[must_use]
+    pub fn contains(&self, date: &NaiveDate) -> bool {
+        self.begin_date <= *date && *date <= self.end_date
+    }
+
+    /// Gets the teaching week of given date, counting from 1 for the week containing the begin date, with weeks starting on Monday. `None` if the date is outside this semester.
+    #[must_use]
+    pub fn week_of(&self, date: &NaiveDate) -> Option<u32> {
+        if !self.contains(date) {
+            return None;
+        }
+        let days = (*date - self.first_monday()).num_days();
+        u32::try_from(days / 7 + 1).ok()
+    }
+
+    /// Gets the number of teaching weeks.
+    #[must_use]
+    pub fn weeks(&self) -> u32 {
+        self.week_of(&self.end_date).unwrap_or_default()
+    }
+
+    /// Gets the first and last date of given teaching week, from Monday to Sunday but clamped to this semester. `None` if there is no such week.
+    #[must_use]
+    pub fn week_range(&self, week: u32) -> Option<(NaiveDate, NaiveDate)> {
+        if week == 0 || week > self.weeks() {
+            return None;
+        }
+        let monday = self.first_monday() + Days::new(u64::from(week - 1) * 7);
+        let sunday = monday + Days::new(6);
+        Some((monday.max(self.begin_date), sunday.min(self.end_date)))
+    }
+
+    /// Gets the Monday of the first teaching week.
+    fn first_monday(&self) -> NaiveDate {
+        let weekday = self.begin_date.weekday().num_days_from_monday();
+        self.begin_date - Days::new(weekday.into())
+    }
 }
 
 impl fmt::Display for Semester {
@@ -245,7 +418,7 @@ impl fmt::Display for Schedule {
             end_time,
             ..
         } = self;
-        let indicator = if *checked_in { "[✓]" } else { "[ ]" };
+        let indicator = status_indicator(*checked_in);
         let (begin_time, end_time) = (
             super::util::format_datetime_to_str(begin_time),
             super::util::format_datetime_to_str(end_time),
@@ -263,23 +436,17 @@ impl fmt::Display for DailySchedule {
         let Self { date, schedules } = self;
         writeln!(f, "Schedule on {date}:")?;
         for schedule in schedules {
-            let Schedule {
-                id,
-                uuid,
-                begin_time,
-                end_time,
-                ..
-            } = schedule;
-            let (begin_time, end_time) = (
-                super::util::format_datetime_to_str(begin_time),
-                super::util::format_datetime_to_str(end_time),
-            );
-            writeln!(
-                f,
-                "  [{begin_time} ~ {end_time}] id={id} uuid={uuid} {}",
-                schedule.course.course_name
-            )?;
+            writeln!(f, "  {schedule}")?;
         }
         Ok(())
     }
 }
+
+/// Indicator of check-in status in human-readable output.
+const fn status_indicator(checked_in: Option<bool>) -> &'static str {
+    match checked_in {
+        Some(true) => "[✓]",
+        Some(false) => "[ ]",
+        None => "[?]",
+    }
+}
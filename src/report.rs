@@ -0,0 +1,205 @@
+//! Per-course attendance, joining the schedule with recorded check-in attempts.
+
+use super::{
+    history::{CheckInEntry, Outcome},
+    output::AttendanceRecord,
+};
+use chrono::{DateTime, Utc};
+use ucas_iclass::{Course, Schedule};
+
+/// Computes the attendance of each course from classes that have ended by `now`.
+///
+/// A class counts as attended if the schedule marks it checked in, or a successful attempt for it is recorded. Courses are listed in given order, followed by those only found in the schedule.
+///
+/// Failed attempts are only counted for the classes given that have ended, so attempts for classes still running or outside the queried range are left out.
+pub fn attendance(
+    courses: &[Course],
+    schedules: &[Schedule],
+    entries: &[CheckInEntry],
+    now: DateTime<Utc>,
+) -> Vec<AttendanceRecord> {
+    let mut records: Vec<AttendanceRecord> = courses.iter().map(empty_record).collect();
+    for schedule in schedules.iter().filter(|schedule| schedule.end_time <= now) {
+        let position = records
+            .iter()
+            .position(|record| record.course_id == schedule.course.course_id);
+        let index = position.unwrap_or_else(|| {
+            records.push(empty_record(&schedule.course));
+            records.len() - 1
+        });
+        let record = &mut records[index];
+        let attempts: Vec<_> = entries
+            .iter()
+            .filter(|entry| entry.is_for(schedule))
+            .collect();
+        record.classes += 1;
+        if schedule.checked_in == Some(true)
+            || attempts.iter().any(|entry| entry.outcome.is_checked_in())
+        {
+            record.attended += 1;
+        } else {
+            record.missed.push(schedule.clone());
+        }
+        record.failed_attempts += attempts
+            .iter()
+            .filter(|entry| matches!(entry.outcome, Outcome::Failed | Outcome::Error))
+            .count();
+    }
+    for record in &mut records {
+        record.attendance_rate = rate(record.attended, record.classes);
+    }
+    records
+}
+
+/// An attendance record of given course without classes.
+fn empty_record(course: &Course) -> AttendanceRecord {
+    AttendanceRecord {
+        course_id: course.course_id.clone(),
+        course_name: course.course_name.clone(),
+        classes: 0,
+        attended: 0,
+        attendance_rate: None,
+        failed_attempts: 0,
+        missed: Vec::new(),
+    }
+}
+
+/// Ratio of attended classes, if any.
+#[allow(clippy::cast_precision_loss, reason = "class counts are small")]
+fn rate(attended: usize, classes: usize) -> Option<f64> {
+    (classes > 0).then(|| attended as f64 / classes as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::attendance;
+    use crate::history::{CheckInEntry, Outcome};
+    use chrono::{DateTime, TimeZone, Utc};
+    use ucas_iclass::{Course, Schedule, util::CST_TIMEZONE};
+
+    fn course(id: &str) -> Course {
+        Course {
+            course_id: id.to_string(),
+            course_name: format!("Course {id}"),
+            classroom_name: "A101".to_string(),
+            teacher_name: "T".to_string(),
+        }
+    }
+
+    /// A class of given course on 2025-10-13 from given hour, for 95 minutes.
+    fn schedule(course_id: &str, id: &str, hour: u32, checked_in: Option<bool>) -> Schedule {
+        let begin_time = CST_TIMEZONE
+            .with_ymd_and_hms(2025, 10, 13, hour, 0, 0)
+            .unwrap();
+        Schedule {
+            course: course(course_id),
+            id: id.parse().unwrap(),
+            uuid: format!("{id:0>32}").parse().unwrap(),
+            checked_in,
+            begin_time,
+            end_time: begin_time + chrono::Duration::minutes(95),
+        }
+    }
+
+    fn entry(by: &str, schedule: &str, outcome: Outcome) -> CheckInEntry {
+        CheckInEntry {
+            version: 1,
+            time: CST_TIMEZONE
+                .with_ymd_and_hms(2025, 10, 13, 8, 0, 0)
+                .unwrap(),
+            student_no: None,
+            by: by.to_string(),
+            schedule: schedule.to_string(),
+            course_id: None,
+            course_name: None,
+            timestamp: 1_760_313_600_000,
+            offset_millis: 0,
+            outcome,
+            sign_id: None,
+            error: None,
+        }
+    }
+
+    /// 2025-10-13 12:00 in China Standard Time, after the classes at 8 and 10.
+    fn noon() -> DateTime<Utc> {
+        CST_TIMEZONE
+            .with_ymd_and_hms(2025, 10, 13, 12, 0, 0)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn attended_via_schedule() {
+        let schedules = [schedule("C1", "1", 8, Some(true))];
+        let records = attendance(&[course("C1")], &schedules, &[], noon());
+        assert_eq!(records[0].classes, 1);
+        assert_eq!(records[0].attended, 1);
+        assert_eq!(records[0].attendance_rate, Some(1.0));
+        assert!(records[0].missed.is_empty());
+    }
+
+    #[test]
+    fn attended_via_history() {
+        let schedules = [
+            schedule("C1", "1", 8, Some(false)),
+            schedule("C1", "2", 10, None),
+        ];
+        let entries = [
+            entry("uuid", &format!("{:0>32}", 1), Outcome::Success),
+            entry("id", "2", Outcome::AlreadyCheckedIn),
+        ];
+        let records = attendance(&[course("C1")], &schedules, &entries, noon());
+        assert_eq!(records[0].classes, 2);
+        assert_eq!(records[0].attended, 2);
+        assert_eq!(records[0].failed_attempts, 0);
+    }
+
+    #[test]
+    fn missed() {
+        let schedules = [
+            schedule("C1", "1", 8, Some(false)),
+            schedule("C1", "2", 10, Some(true)),
+        ];
+        // An attempt for another class does not count
+        let entries = [entry("id", "3", Outcome::Success)];
+        let records = attendance(&[course("C1")], &schedules, &entries, noon());
+        assert_eq!(records[0].attended, 1);
+        assert_eq!(records[0].attendance_rate, Some(0.5));
+        assert_eq!(records[0].missed.len(), 1);
+        assert_eq!(records[0].missed[0].id.as_str(), "1");
+    }
+
+    #[test]
+    fn failed_attempts() {
+        let schedules = [
+            schedule("C1", "1", 8, None),
+            schedule("C1", "2", 10, None),
+            // Still running at noon
+            schedule("C1", "3", 11, None),
+        ];
+        let entries = [
+            entry("id", "1", Outcome::Failed),
+            entry("id", "1", Outcome::Error),
+            entry("id", "2", Outcome::Error),
+            entry("id", "2", Outcome::Success),
+            entry("id", "3", Outcome::Failed),
+            // Outside the queried schedules
+            entry("id", "4", Outcome::Failed),
+        ];
+        let records = attendance(&[course("C1")], &schedules, &entries, noon());
+        assert_eq!(records[0].classes, 2);
+        assert_eq!(records[0].attended, 1);
+        assert_eq!(records[0].missed.len(), 1);
+        assert_eq!(records[0].failed_attempts, 3);
+    }
+
+    #[test]
+    fn course_order() {
+        let schedules = [schedule("C3", "1", 8, None)];
+        let records = attendance(&[course("C2"), course("C1")], &schedules, &[], noon());
+        let ids: Vec<_> = records.iter().map(|r| r.course_id.as_str()).collect();
+        assert_eq!(ids, ["C2", "C1", "C3"]);
+        assert_eq!(records[0].classes, 0);
+        assert_eq!(records[0].attendance_rate, None);
+    }
+}
@@ -0,0 +1,223 @@
+//! Typed schedule identifiers and check-in targets.
+
+use super::IClassError;
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+/// Length of a schedule uuid in hexadecimal digits.
+const UUID_LENGTH: usize = 32;
+
+/// Id of a schedule (`courseSchedId`), all digits. Used by the QR code on the computer inside the classroom.
+///
+/// Parsing with [`FromStr`] checks the format, while values deserialized from API responses are taken as is. Ids are ordered numerically, so `"9"` comes before `"10"`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ScheduleId(String);
+
+/// Unique id of a schedule (`timeTableId`), 32 hexadecimal digits. Used by the QR code on the smart device outside the classroom.
+///
+/// Parsing with [`FromStr`] checks the format, while values deserialized from API responses are taken as is.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ScheduleUuid(String);
+
+/// The schedule to check in, either by id or by uuid.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CheckInTarget {
+    /// Check in by schedule uuid, like scanning the QR code outside the classroom.
+    Uuid(ScheduleUuid),
+    /// Check in by schedule id, like scanning the QR code inside the classroom.
+    Id(ScheduleId),
+}
+
+impl ScheduleId {
+    /// The id as a string slice.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl ScheduleUuid {
+    /// The uuid as a string slice.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl CheckInTarget {
+    /// Kind of the target, `"uuid"` or `"id"`.
+    #[must_use]
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::Uuid(_) => "uuid",
+            Self::Id(_) => "id",
+        }
+    }
+
+    /// The uuid or id as a string slice.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Uuid(uuid) => uuid.as_str(),
+            Self::Id(id) => id.as_str(),
+        }
+    }
+}
+
+impl Ord for ScheduleId {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Comparing lengths first orders digit strings numerically, and the original strings break ties between leading zeros
+        let (this, that) = (
+            self.0.trim_start_matches('0'),
+            other.0.trim_start_matches('0'),
+        );
+        this.len()
+            .cmp(&that.len())
+            .then_with(|| this.cmp(that))
+            .then_with(|| self.0.cmp(&other.0))
+    }
+}
+
+impl PartialOrd for ScheduleId {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl FromStr for ScheduleId {
+    type Err = IClassError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.is_empty() && s.chars().all(|c| c.is_ascii_digit()) {
+            Ok(Self(s.to_string()))
+        } else {
+            Err(IClassError::InvalidCheckInTarget(s.to_string()))
+        }
+    }
+}
+
+impl FromStr for ScheduleUuid {
+    type Err = IClassError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() == UUID_LENGTH && s.chars().all(|c| c.is_ascii_hexdigit()) {
+            Ok(Self(s.to_string()))
+        } else {
+            Err(IClassError::InvalidCheckInTarget(s.to_string()))
+        }
+    }
+}
+
+impl FromStr for CheckInTarget {
+    type Err = IClassError;
+    /// Parses a schedule uuid (32 hexadecimal digits) or id (all digits). An all-digit string of uuid length is taken as a uuid.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse()
+            .map(Self::Uuid)
+            .or_else(|_| s.parse().map(Self::Id))
+    }
+}
+
+impl From<ScheduleUuid> for CheckInTarget {
+    fn from(uuid: ScheduleUuid) -> Self {
+        Self::Uuid(uuid)
+    }
+}
+
+impl From<ScheduleId> for CheckInTarget {
+    fn from(id: ScheduleId) -> Self {
+        Self::Id(id)
+    }
+}
+
+impl fmt::Display for ScheduleId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl fmt::Display for ScheduleUuid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl fmt::Display for CheckInTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.kind(), self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CheckInTarget, IClassError, ScheduleId, ScheduleUuid};
+
+    const UUID: &str = "0123456789abcdefABCDEF0123456789";
+
+    fn id(s: &str) -> ScheduleId {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_targets() {
+        let target: CheckInTarget = UUID.parse().unwrap();
+        assert_eq!(target, CheckInTarget::Uuid(UUID.parse().unwrap()));
+        assert_eq!(target.kind(), "uuid");
+        assert_eq!(target.to_string(), format!("uuid={UUID}"));
+
+        let target: CheckInTarget = "123456".parse().unwrap();
+        assert_eq!(target, CheckInTarget::Id(id("123456")));
+        assert_eq!(target.kind(), "id");
+        assert_eq!(target.to_string(), "id=123456");
+
+        // All digits of uuid length is a uuid
+        let digits = "1".repeat(32);
+        assert!(matches!(
+            digits.parse::<CheckInTarget>().unwrap(),
+            CheckInTarget::Uuid(_)
+        ));
+    }
+
+    #[test]
+    fn invalid_targets() {
+        for s in [
+            "",
+            "12a",
+            "-1",
+            " 123",
+            "0123456789abcdef0123456789abcdeg",
+            "0123456789abcdef0123456789abcde",
+            "0123456789abcdef0123456789abcdef0",
+        ] {
+            assert!(
+                matches!(
+                    s.parse::<CheckInTarget>(),
+                    Err(IClassError::InvalidCheckInTarget(ref target)) if target == s
+                ),
+                "{s:?} should be rejected"
+            );
+        }
+        assert!("12a".parse::<ScheduleId>().is_err());
+        assert!("123".parse::<ScheduleUuid>().is_err());
+        assert!(UUID.parse::<ScheduleId>().is_err());
+    }
+
+    #[test]
+    fn ids_ordered_numerically() {
+        assert!(id("9") < id("10"));
+        assert!(id("99") < id("100"));
+        assert!(id("10") > id("9"));
+        // Leading zeros do not change the numeric order
+        assert!(id("009") < id("10"));
+        assert!(id("0010") > id("9"));
+        // Equal numbers are told apart by the original strings, consistently with `Eq`
+        assert_ne!(id("010"), id("10"));
+        assert_eq!(id("010").cmp(&id("10")), "010".cmp("10"));
+        assert_eq!(id("0").cmp(&id("00")), "0".cmp("00"));
+
+        let mut ids = [id("100"), id("9"), id("010"), id("10"), id("0")];
+        ids.sort();
+        let sorted: Vec<_> = ids.iter().map(ScheduleId::as_str).collect();
+        assert_eq!(sorted, ["0", "9", "010", "10", "100"]);
+    }
+}
@@ -0,0 +1,225 @@
+//! HTTP transports behind [`IClass`](super::IClass).
+//!
+//! [`IClass`](super::IClass) talks to the iClass API through the [`Transport`] trait, so that it is not tied to a specific HTTP client or async runtime. Available implementations:
+//!
+//! - [`CyperTransport`], based on `cyper` and the `compio` runtime (feature `cyper`, enabled by default).
+//! - [`ReqwestTransport`], based on `reqwest` and the `tokio` runtime (feature `reqwest`).
+//! - [`MemoryTransport`], answering requests with a closure, for unit tests.
+
+#[cfg(feature = "cyper")]
+mod cyper;
+mod memory;
+#[cfg(feature = "reqwest")]
+mod reqwest;
+
+#[cfg(feature = "cyper")]
+pub use self::cyper::CyperTransport;
+pub use self::memory::MemoryTransport;
+#[cfg(feature = "reqwest")]
+pub use self::reqwest::ReqwestTransport;
+
+use super::IClassError;
+use http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, header::USER_AGENT};
+use std::time::Duration;
+use url::Url;
+
+/// The transport used by [`IClass`](super::IClass) unless specified otherwise: [`CyperTransport`] if the `cyper` feature is enabled, else [`ReqwestTransport`] if the `reqwest` feature is enabled, else [`MemoryTransport`].
+#[cfg(feature = "cyper")]
+pub type DefaultTransport = CyperTransport;
+/// The transport used by [`IClass`](super::IClass) unless specified otherwise: [`CyperTransport`] if the `cyper` feature is enabled, else [`ReqwestTransport`] if the `reqwest` feature is enabled, else [`MemoryTransport`].
+#[cfg(all(not(feature = "cyper"), feature = "reqwest"))]
+pub type DefaultTransport = ReqwestTransport;
+/// The transport used by [`IClass`](super::IClass) unless specified otherwise: [`CyperTransport`] if the `cyper` feature is enabled, else [`ReqwestTransport`] if the `reqwest` feature is enabled, else [`MemoryTransport`].
+#[cfg(all(not(feature = "cyper"), not(feature = "reqwest")))]
+pub type DefaultTransport = MemoryTransport;
+
+/// Sends requests to the iClass API.
+///
+/// Futures of [`IClass`](super::IClass) are `Send` whenever the transport is `Sync` and its futures are `Send`, so that they can be spawned on multi-threaded runtimes. This holds for [`ReqwestTransport`] and [`MemoryTransport`], but not for [`CyperTransport`], which runs on the thread-per-core `compio` runtime. The trait itself does not require it, thus the generic `impl` blocks of [`IClass`](super::IClass) allow `clippy::future_not_send`.
+pub trait Transport {
+    /// Sends given request and reads the whole response body.
+    ///
+    /// # Errors
+    ///
+    /// Network errors, timeouts and the like, in the variant of [`IClassError`] matching the underlying client.
+    fn send(
+        &self,
+        request: TransportRequest,
+    ) -> impl Future<Output = Result<TransportResponse, IClassError>>;
+
+    /// Sleeps for given duration on the runtime of the transport.
+    fn sleep(&self, duration: Duration) -> impl Future<Output = ()>;
+}
+
+/// A [`Transport`] backed by a real HTTP client, which can be created from a [`TransportConfig`].
+pub trait HttpTransport: Transport + Sized {
+    /// Creates the transport from given configuration.
+    ///
+    /// # Errors
+    ///
+    /// If any header, the proxy or TLS configuration is invalid, or the TLS backend fails to initialize.
+    fn from_config(config: &TransportConfig) -> Result<Self, IClassError>;
+}
+
+/// A request to the iClass API.
+#[derive(Clone, Debug)]
+pub struct TransportRequest {
+    /// HTTP method.
+    pub method: Method,
+    /// URL, including query parameters.
+    pub url: Url,
+    /// Request headers.
+    pub headers: Vec<(String, String)>,
+    /// URL-encoded form fields sent as the body, if any.
+    pub form: Option<Vec<(String, String)>>,
+}
+
+/// A response from the iClass API.
+#[derive(Clone, Debug)]
+pub struct TransportResponse {
+    /// HTTP status code.
+    pub status: StatusCode,
+    /// Response headers.
+    pub headers: HeaderMap,
+    /// Response body.
+    pub body: Vec<u8>,
+}
+
+/// Configuration of an [`HttpTransport`], usually set through [`IClassBuilder`](super::IClassBuilder).
+#[derive(Clone)]
+pub struct TransportConfig {
+    /// Timeout for each request, including reading the response.
+    pub timeout: Option<Duration>,
+    /// User agent.
+    pub user_agent: Option<String>,
+    /// Extra headers sent with every request.
+    pub headers: Vec<(String, String)>,
+    /// Proxy URL.
+    pub proxy: Option<Url>,
+    /// Username and password for the proxy.
+    pub proxy_auth: Option<(String, String)>,
+    /// Hosts that bypass the proxy, as a comma-separated list.
+    pub no_proxy: Option<String>,
+    /// Whether to use proxies from environment variables if no proxy is set.
+    pub system_proxy: bool,
+    /// Extra trusted root certificates in PEM format.
+    pub root_certificates: Vec<Vec<u8>>,
+    /// Client certificate chain and private key in PEM format.
+    pub client_identity: Option<(Vec<u8>, Vec<u8>)>,
+    /// Whether to accept invalid server certificates.
+    pub accept_invalid_certs: bool,
+}
+
+impl TransportRequest {
+    /// Creates a `GET` request to given URL.
+    #[must_use]
+    pub const fn get(url: Url) -> Self {
+        Self::new(Method::GET, url)
+    }
+
+    /// Creates a `POST` request to given URL.
+    #[must_use]
+    pub const fn post(url: Url) -> Self {
+        Self::new(Method::POST, url)
+    }
+
+    /// Creates a request with given method and URL.
+    #[must_use]
+    pub const fn new(method: Method, url: Url) -> Self {
+        Self {
+            method,
+            url,
+            headers: Vec::new(),
+            form: None,
+        }
+    }
+
+    /// Adds a header.
+    #[must_use]
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Appends query parameters to the URL.
+    #[must_use]
+    pub fn query<V: AsRef<str>>(mut self, params: &[(&str, V)]) -> Self {
+        self.url.query_pairs_mut().extend_pairs(params);
+        self
+    }
+
+    /// Sets the URL-encoded form fields sent as the body.
+    #[must_use]
+    pub fn form<V: AsRef<str>>(mut self, fields: &[(&str, V)]) -> Self {
+        self.form = Some(
+            fields
+                .iter()
+                .map(|(k, v)| ((*k).to_string(), v.as_ref().to_string()))
+                .collect(),
+        );
+        self
+    }
+
+    /// Encodes the form fields, if any.
+    #[must_use]
+    pub fn encoded_form(&self) -> Option<String> {
+        self.form.as_ref().map(|fields| {
+            url::form_urlencoded::Serializer::new(String::new())
+                .extend_pairs(fields)
+                .finish()
+        })
+    }
+}
+
+impl TransportResponse {
+    /// Creates a `200 OK` response with given JSON body, like what the iClass API returns.
+    #[must_use]
+    pub fn json(body: &serde_json::Value) -> Self {
+        Self {
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+            body: body.to_string().into_bytes(),
+        }
+    }
+}
+
+impl Default for TransportConfig {
+    fn default() -> Self {
+        Self {
+            timeout: None,
+            user_agent: None,
+            headers: Vec::new(),
+            proxy: None,
+            proxy_auth: None,
+            no_proxy: None,
+            system_proxy: true,
+            root_certificates: Vec::new(),
+            client_identity: None,
+            accept_invalid_certs: false,
+        }
+    }
+}
+
+impl TransportConfig {
+    /// Combines the user agent and extra headers into a header map.
+    ///
+    /// # Errors
+    ///
+    /// [`IClassError::HttpError`] if the user agent or any header is invalid.
+    pub fn default_headers(&self) -> Result<HeaderMap, IClassError> {
+        let mut headers = HeaderMap::new();
+        if let Some(user_agent) = &self.user_agent {
+            headers.insert(USER_AGENT, parse_header_value(user_agent)?);
+        }
+        for (name, value) in &self.headers {
+            let name = HeaderName::try_from(name.as_str()).map_err(http::Error::from)?;
+            headers.append(name, parse_header_value(value)?);
+        }
+        Ok(headers)
+    }
+}
+
+/// Parses a header value.
+fn parse_header_value(value: &str) -> Result<HeaderValue, http::Error> {
+    Ok(HeaderValue::try_from(value)?)
+}
@@ -0,0 +1,97 @@
+//! Transport based on `cyper` and the `compio` runtime.
+
+#![allow(
+    clippy::future_not_send,
+    reason = "`compio` is a thread-per-core runtime"
+)]
+
+use super::{
+    super::{IClassError, tls},
+    HttpTransport, Transport, TransportConfig, TransportRequest, TransportResponse,
+};
+use cyper::{
+    Client, Error as CyperError,
+    proxy::{NoProxy, Proxy},
+};
+use http::header::CONTENT_TYPE;
+use std::{sync::Arc, time::Duration};
+
+/// A [`Transport`] based on `cyper`, running on the `compio` runtime.
+#[derive(Clone, Debug)]
+pub struct CyperTransport {
+    /// The HTTP client.
+    client: Client,
+    /// Timeout for each request.
+    timeout: Option<Duration>,
+}
+
+impl CyperTransport {
+    /// Creates a transport from an existing client, with given timeout for each request.
+    #[must_use]
+    pub const fn with_client(client: Client, timeout: Option<Duration>) -> Self {
+        Self { client, timeout }
+    }
+
+    /// Sends given request without the timeout.
+    async fn send_inner(&self, request: TransportRequest) -> Result<TransportResponse, CyperError> {
+        let mut builder = self
+            .client
+            .request(request.method.clone(), request.url.clone())?;
+        for (name, value) in &request.headers {
+            builder = builder.header(name, value)?;
+        }
+        if let Some(form) = request.encoded_form() {
+            builder = builder
+                .header(CONTENT_TYPE, "application/x-www-form-urlencoded")?
+                .body(form);
+        }
+        let response = builder.send().await?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.bytes().await?.to_vec();
+        Ok(TransportResponse {
+            status,
+            headers,
+            body,
+        })
+    }
+}
+
+impl Transport for CyperTransport {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse, IClassError> {
+        let response = match self.timeout {
+            Some(timeout) => compio::time::timeout(timeout, self.send_inner(request))
+                .await
+                .map_err(|_| CyperError::Timeout)?,
+            None => self.send_inner(request).await,
+        };
+        Ok(response?)
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        compio::time::sleep(duration).await;
+    }
+}
+
+impl HttpTransport for CyperTransport {
+    fn from_config(config: &TransportConfig) -> Result<Self, IClassError> {
+        let mut client = Client::builder().default_headers(config.default_headers()?);
+        if let Some(url) = config.proxy.clone() {
+            let mut proxy = Proxy::all(url)?;
+            if let Some((username, password)) = &config.proxy_auth {
+                proxy = proxy.basic_auth(username, password);
+            }
+            let no_proxy = config
+                .no_proxy
+                .as_deref()
+                .map_or_else(NoProxy::from_env, NoProxy::from_string);
+            client = client.proxy(proxy.no_proxy(no_proxy));
+        } else if !config.system_proxy {
+            client = client.no_proxy();
+        }
+        let client = client
+            .use_rustls(Arc::new(tls::client_config(config)?))
+            .build()?;
+        Ok(Self::with_client(client, config.timeout))
+    }
+}
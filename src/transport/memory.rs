@@ -0,0 +1,142 @@
+//! In-memory transport for unit tests.
+
+use super::{IClassError, Transport, TransportRequest, TransportResponse};
+use std::{
+    fmt,
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+
+/// Handler answering requests of a [`MemoryTransport`].
+type Handler = dyn Fn(&TransportRequest) -> Result<TransportResponse, IClassError> + Send + Sync;
+
+/// A [`Transport`] answering requests with a closure instead of sending them over the network, recording all requests. Sleeping returns immediately.
+pub struct MemoryTransport {
+    /// The handler.
+    handler: Box<Handler>,
+    /// Requests received so far.
+    requests: Mutex<Vec<TransportRequest>>,
+}
+
+impl MemoryTransport {
+    /// Creates a transport answering requests with given handler.
+    pub fn new<F>(handler: F) -> Self
+    where
+        F: Fn(&TransportRequest) -> Result<TransportResponse, IClassError> + Send + Sync + 'static,
+    {
+        Self {
+            handler: Box::new(handler),
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Gets a copy of the requests received so far.
+    #[must_use]
+    pub fn requests(&self) -> Vec<TransportRequest> {
+        self.requests
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+impl Transport for MemoryTransport {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse, IClassError> {
+        let response = (self.handler)(&request);
+        self.requests
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(request);
+        response
+    }
+
+    async fn sleep(&self, _duration: Duration) {}
+}
+
+impl fmt::Debug for MemoryTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryTransport")
+            .field("requests", &self.requests)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IClassError, MemoryTransport, Transport, TransportRequest, TransportResponse};
+    use http::{Method, StatusCode};
+    use serde_json::json;
+    use std::time::{Duration, Instant};
+    use url::Url;
+
+    fn url(path: &str) -> Url {
+        Url::parse("https://example.com/")
+            .unwrap()
+            .join(path)
+            .unwrap()
+    }
+
+    fn transport() -> MemoryTransport {
+        MemoryTransport::new(|request| {
+            if request.url.path() == "/fail" {
+                Err(IClassError::DataParsingError)
+            } else {
+                Ok(TransportResponse::json(
+                    &json!({ "path": request.url.path() }),
+                ))
+            }
+        })
+    }
+
+    #[compio::test]
+    async fn answers_and_records_requests() {
+        let transport = transport();
+        let response = transport
+            .send(
+                TransportRequest::post(url("a"))
+                    .header("sessionId", "s")
+                    .form(&[("id", "42")]),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(response.body, br#"{"path":"/a"}"#);
+        transport
+            .send(TransportRequest::get(url("b")).query(&[("x", "1")]))
+            .await
+            .unwrap();
+
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].method, Method::POST);
+        assert_eq!(requests[0].headers, [("sessionId".into(), "s".into())]);
+        assert_eq!(requests[0].encoded_form().as_deref(), Some("id=42"));
+        assert_eq!(requests[1].method, Method::GET);
+        assert_eq!(requests[1].url.query(), Some("x=1"));
+    }
+
+    #[compio::test]
+    async fn records_failed_requests() {
+        let transport = transport();
+        assert!(matches!(
+            transport.send(TransportRequest::get(url("fail"))).await,
+            Err(IClassError::DataParsingError)
+        ));
+        assert_eq!(transport.requests()[0].url.path(), "/fail");
+    }
+
+    #[compio::test]
+    async fn sleep_returns_immediately() {
+        let start = Instant::now();
+        transport().sleep(Duration::from_mins(1)).await;
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn futures_are_send() {
+        fn assert_send<F: Future + Send>(_: F) {}
+        let transport = transport();
+        assert_send(transport.send(TransportRequest::get(url("a"))));
+        assert_send(transport.sleep(Duration::ZERO));
+    }
+}
@@ -0,0 +1,87 @@
+//! Transport based on `reqwest` and the `tokio` runtime.
+
+use super::{
+    super::{IClassError, tls},
+    HttpTransport, Transport, TransportConfig, TransportRequest, TransportResponse,
+};
+use http::header::CONTENT_TYPE;
+use reqwest::{Client, Error as ReqwestError, NoProxy, Proxy};
+use std::time::Duration;
+
+/// A [`Transport`] based on `reqwest`, running on the `tokio` runtime.
+#[derive(Clone, Debug)]
+pub struct ReqwestTransport {
+    /// The HTTP client.
+    client: Client,
+}
+
+impl ReqwestTransport {
+    /// Creates a transport from an existing client.
+    #[must_use]
+    pub const fn with_client(client: Client) -> Self {
+        Self { client }
+    }
+
+    /// Sends given request.
+    async fn send_inner(
+        &self,
+        request: TransportRequest,
+    ) -> Result<TransportResponse, ReqwestError> {
+        let mut builder = self
+            .client
+            .request(request.method.clone(), request.url.clone());
+        for (name, value) in &request.headers {
+            builder = builder.header(name, value);
+        }
+        if let Some(form) = request.encoded_form() {
+            builder = builder
+                .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+                .body(form);
+        }
+        let response = builder.send().await?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.bytes().await?.to_vec();
+        Ok(TransportResponse {
+            status,
+            headers,
+            body,
+        })
+    }
+}
+
+impl Transport for ReqwestTransport {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse, IClassError> {
+        Ok(self.send_inner(request).await?)
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        tokio::time::sleep(duration).await;
+    }
+}
+
+impl HttpTransport for ReqwestTransport {
+    fn from_config(config: &TransportConfig) -> Result<Self, IClassError> {
+        let mut client = Client::builder().default_headers(config.default_headers()?);
+        if let Some(timeout) = config.timeout {
+            client = client.timeout(timeout);
+        }
+        if let Some(url) = config.proxy.clone() {
+            let mut proxy = Proxy::all(url)?;
+            if let Some((username, password)) = &config.proxy_auth {
+                proxy = proxy.basic_auth(username, password);
+            }
+            let no_proxy = config
+                .no_proxy
+                .as_deref()
+                .map_or_else(NoProxy::from_env, NoProxy::from_string);
+            client = client.proxy(proxy.no_proxy(no_proxy));
+        } else if !config.system_proxy {
+            client = client.no_proxy();
+        }
+        let client = client
+            .tls_backend_preconfigured(tls::client_config(config)?)
+            .build()?;
+        Ok(Self::with_client(client))
+    }
+}
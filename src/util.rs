@@ -1,7 +1,10 @@
 //! Utility functions.
 
 use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, Utc};
-use serde::de::{self, Deserialize, Deserializer};
+use serde::{
+    Serializer,
+    de::{self, Deserialize, Deserializer},
+};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 /// UTC+8 timezone for China Standard Time.
@@ -24,6 +27,26 @@ where
     }
 }
 
+/// Deserialize an optional string (1/0) to an optional boolean.
+///
+/// # Errors
+///
+/// If the string is not "1" or "0".
+pub fn deserialize_opt_str_to_bool<'de, D>(deserializer: D) -> Result<Option<bool>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: Option<&str> = Deserialize::deserialize(deserializer)?;
+    s.map(|str_val| match str_val {
+        "1" => Ok(true),
+        "0" => Ok(false),
+        _ => Err(de::Error::custom(format!(
+            "invalid boolean string: {str_val}"
+        ))),
+    })
+    .transpose()
+}
+
 /// Deserialize a string to an i8.
 ///
 /// # Errors
@@ -113,6 +136,47 @@ where
     Ok(naive_date)
 }
 
+/// Serialize a [`NaiveDate`] to a string (YYYYMMDD).
+///
+/// # Errors
+///
+/// Errors of the serializer.
+pub fn serialize_date_to_str<S>(date: &NaiveDate, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&format_date_to_str(date))
+}
+
+/// Serialize a [`NaiveDate`] to a string (YYYY-MM-DD).
+///
+/// # Errors
+///
+/// Errors of the serializer.
+pub fn serialize_date_to_str_hyphen<S>(date: &NaiveDate, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_str(&date.format("%Y-%m-%d"))
+}
+
+/// Serialize a [`DateTime`] to a string (YYYY-MM-DD HH:MM:SS) in China Standard Time.
+///
+/// # Errors
+///
+/// Errors of the serializer.
+pub fn serialize_datetime_to_str<S>(
+    datetime: &DateTime<FixedOffset>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&format_datetime_to_str(
+        &datetime.with_timezone(&CST_TIMEZONE),
+    ))
+}
+
 /// Format a [`NaiveDate`] to a string (YYYYMMDD).
 #[must_use]
 pub fn format_date_to_str(date: &NaiveDate) -> String {
@@ -125,7 +189,7 @@ pub fn format_datetime_to_str(datetime: &DateTime<FixedOffset>) -> String {
     datetime.format("%Y-%m-%d %H:%M:%S").to_string()
 }
 
-/// Get current timestamp in milliseconds since UNIX epoch.
+/// Get current timestamp in milliseconds since UNIX epoch from the system clock. See [`Clock`](crate::Clock) for a replaceable source of time.
 ///
 /// # Panics
 ///
@@ -138,7 +202,7 @@ pub fn current_timestamp_millis() -> u128 {
         .as_millis()
 }
 
-/// Get today.
+/// Get today in China Standard Time from the system clock. See [`Clock`](crate::Clock) for a replaceable source of time.
 #[must_use]
 pub fn get_today() -> NaiveDate {
     Utc::now().with_timezone(&CST_TIMEZONE).date_naive()
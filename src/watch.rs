@@ -0,0 +1,536 @@
+//! Daemon mode checking in automatically for every class of the day.
+
+use super::{
+    history::{CheckInEntry, History},
+    output::{LogRecord, Output},
+};
+use anyhow::Result;
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{File, OpenOptions},
+    hash::{BuildHasher, RandomState},
+    io::Write,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time,
+};
+use ucas_iclass::{
+    CheckInPolicy, Clock, IClass, IClassError, Schedule, ScheduleUuid,
+    transport::{DefaultTransport, Transport},
+    util::{CST_TIMEZONE, format_datetime_to_str},
+};
+
+/// Longest single sleep, so that shutdown requests and clock jumps are noticed in time.
+const SLEEP_SLICE: time::Duration = time::Duration::from_secs(1);
+/// Delay before retrying a failed check-in or schedule refresh.
+const RETRY_DELAY: Duration = Duration::minutes(1);
+/// Maximum number of check-in attempts for a single class.
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Options of the watcher.
+pub struct WatchOptions {
+    /// When the check-in window of each class opens and closes.
+    pub policy: CheckInPolicy,
+    /// Maximum random delay added to the opening of each window.
+    pub jitter: Duration,
+    /// Interval between schedule refreshes.
+    pub refresh: Duration,
+}
+
+/// Check-in state of a pending class.
+struct Pending {
+    /// When to attempt checking in next.
+    next_attempt: DateTime<FixedOffset>,
+    /// Failed attempts so far.
+    attempts: u32,
+}
+
+/// Watches the schedule of the day and checks in automatically.
+pub struct Watcher<'a, T = DefaultTransport> {
+    /// The iClass instance.
+    iclass: &'a IClass<T>,
+    /// Options.
+    options: WatchOptions,
+    /// Status log.
+    log: StatusLog<'a>,
+    /// Where check-in attempts are recorded, if anywhere.
+    history: Option<History>,
+    /// Set when shutdown is requested.
+    shutdown: Arc<AtomicBool>,
+    /// Date of the current schedule.
+    date: NaiveDate,
+    /// Schedule of the day.
+    schedules: Vec<Schedule>,
+    /// Classes not checked in yet, by uuid.
+    pending: HashMap<ScheduleUuid, Pending>,
+    /// Classes of the day checked in or given up on, which are never planned again even if the schedule does not show them as checked in.
+    done: HashSet<ScheduleUuid>,
+    /// When to refresh the schedule next.
+    next_refresh: DateTime<FixedOffset>,
+}
+
+impl<'a, T: Transport> Watcher<'a, T> {
+    /// Creates a watcher.
+    pub fn new(
+        iclass: &'a IClass<T>,
+        options: WatchOptions,
+        log: StatusLog<'a>,
+        history: Option<History>,
+        shutdown: Arc<AtomicBool>,
+    ) -> Self {
+        let now = iclass.server_now();
+        Self {
+            iclass,
+            options,
+            log,
+            history,
+            shutdown,
+            date: now.date_naive(),
+            schedules: Vec::new(),
+            pending: HashMap::new(),
+            done: HashSet::new(),
+            next_refresh: now,
+        }
+    }
+
+    /// Runs until shutdown is requested.
+    pub async fn run(mut self) -> Result<()> {
+        self.log.info(&format!(
+            "Watching schedules, check-in window opens {} min before class begins",
+            self.options.policy.lead_time.num_minutes()
+        ));
+        while !self.shutdown.load(Ordering::Relaxed) {
+            let now = self.iclass.server_now();
+            if now.date_naive() != self.date || now >= self.next_refresh {
+                self.refresh(now).await;
+                continue;
+            }
+            if let Some(uuid) = self.due(now) {
+                self.check_in(&uuid).await;
+                continue;
+            }
+            let wake = self
+                .pending
+                .values()
+                .map(|pending| pending.next_attempt)
+                .chain([self.next_refresh, next_midnight(now)])
+                .min()
+                .unwrap_or(self.next_refresh);
+            self.sleep_until(wake).await;
+        }
+        self.log.info("Shutting down");
+        Ok(())
+    }
+
+    /// Refreshes the schedule of today, logging changes.
+    async fn refresh(&mut self, now: DateTime<FixedOffset>) {
+        let today = now.date_naive();
+        match self.iclass.query_daily_schedule(&today).await {
+            Ok(schedules) => {
+                self.next_refresh = now + self.options.refresh;
+                if today != self.date || !same_schedules(&self.schedules, &schedules) {
+                    self.log.info(&format!(
+                        "Schedule on {today}: {} class(es)",
+                        schedules.len()
+                    ));
+                    for schedule in &schedules {
+                        self.log.info(&format!("  {schedule}"));
+                    }
+                }
+                if today != self.date {
+                    self.done.clear();
+                }
+                self.date = today;
+                self.update(schedules, now);
+            }
+            Err(e) => {
+                self.next_refresh = now + RETRY_DELAY;
+                self.log.info(&format!("Failed to refresh schedule: {e}"));
+            }
+        }
+    }
+
+    /// Replaces the schedule, planning check-ins for new classes and dropping those gone, already checked in or done.
+    fn update(&mut self, schedules: Vec<Schedule>, now: DateTime<FixedOffset>) {
+        let mut pending = HashMap::new();
+        for schedule in &schedules {
+            if schedule.checked_in == Some(true)
+                || self.done.contains(&schedule.uuid)
+                || self.options.policy.window_close(schedule) <= now
+            {
+                continue;
+            }
+            let state = if let Some(state) = self.pending.remove(&schedule.uuid) {
+                state
+            } else {
+                let next_attempt =
+                    self.options.policy.window_open(schedule) + random_delay(self.options.jitter);
+                self.log.info(&format!(
+                    "Planned check-in for {} at {}",
+                    schedule.course.course_name,
+                    format_datetime_to_str(&next_attempt)
+                ));
+                Pending {
+                    next_attempt,
+                    attempts: 0,
+                }
+            };
+            pending.insert(schedule.uuid.clone(), state);
+        }
+        self.schedules = schedules;
+        self.pending = pending;
+    }
+
+    /// Finds the uuid of a class due for check-in, preferring the earliest one.
+    fn due(&self, now: DateTime<FixedOffset>) -> Option<ScheduleUuid> {
+        self.schedules
+            .iter()
+            .filter(|schedule| {
+                self.pending
+                    .get(&schedule.uuid)
+                    .is_some_and(|pending| pending.next_attempt <= now)
+            })
+            .min_by_key(|schedule| schedule.begin_time)
+            .map(|schedule| schedule.uuid.clone())
+    }
+
+    /// Checks in the class with given uuid and confirms the result, retrying later on failure.
+    async fn check_in(&mut self, uuid: &ScheduleUuid) {
+        let Some(schedule) = self.schedules.iter().find(|s| &s.uuid == uuid).cloned() else {
+            return;
+        };
+        let name = &schedule.course.course_name;
+        let timestamp = self.iclass.server_timestamp_millis();
+        let result = self.iclass.check_in_by_uuid(uuid, timestamp).await;
+        if let Some(history) = &self.history {
+            history.record(&CheckInEntry::new(
+                self.iclass,
+                "uuid",
+                uuid.as_str(),
+                Some(&schedule.course),
+                timestamp,
+                &result,
+            ));
+        }
+        match result {
+            Ok(result) => {
+                self.log
+                    .info(&format!("Checked in for {name} (uuid={uuid}): {result}"));
+                self.pending.remove(uuid);
+                self.done.insert(uuid.clone());
+                self.confirm(uuid, name).await;
+            }
+            Err(IClassError::AlreadyCheckedIn(_)) => {
+                self.log
+                    .info(&format!("Already checked in for {name} (uuid={uuid})"));
+                self.pending.remove(uuid);
+                self.done.insert(uuid.clone());
+            }
+            Err(e) => {
+                let now = self.iclass.server_now();
+                let close = self.options.policy.window_close(&schedule);
+                let Some(pending) = self.pending.get_mut(uuid) else {
+                    return;
+                };
+                pending.attempts += 1;
+                pending.next_attempt = now + RETRY_DELAY;
+                if pending.attempts >= MAX_ATTEMPTS || pending.next_attempt >= close {
+                    self.log.info(&format!(
+                        "Failed to check in for {name} (uuid={uuid}), giving up: {e}"
+                    ));
+                    self.pending.remove(uuid);
+                    self.done.insert(uuid.clone());
+                } else {
+                    self.log.info(&format!(
+                        "Failed to check in for {name} (uuid={uuid}), retrying in {} min: {e}",
+                        RETRY_DELAY.num_minutes()
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Confirms the check-in status of the class with given uuid by querying the schedule again.
+    async fn confirm(&mut self, uuid: &ScheduleUuid, name: &str) {
+        match self.iclass.query_daily_schedule(&self.date).await {
+            Ok(schedules) => {
+                let checked_in = schedules
+                    .iter()
+                    .any(|s| &s.uuid == uuid && s.checked_in == Some(true));
+                if checked_in {
+                    self.log.info(&format!("Confirmed check-in for {name}"));
+                } else {
+                    self.log.info(&format!(
+                        "Check-in for {name} not reflected in the schedule yet"
+                    ));
+                }
+                self.update(schedules, self.iclass.server_now());
+            }
+            Err(e) => self
+                .log
+                .info(&format!("Failed to confirm check-in for {name}: {e}")),
+        }
+    }
+
+    /// Sleeps until given time or shutdown is requested.
+    async fn sleep_until(&self, wake: DateTime<FixedOffset>) {
+        while !self.shutdown.load(Ordering::Relaxed) {
+            let Ok(remaining) = (wake - self.iclass.server_now()).to_std() else {
+                break;
+            };
+            if remaining.is_zero() {
+                break;
+            }
+            self.iclass
+                .transport()
+                .sleep(remaining.min(SLEEP_SLICE))
+                .await;
+        }
+    }
+}
+
+/// Status log printed to stdout, and optionally appended to a file.
+pub struct StatusLog<'a> {
+    /// Output to print to.
+    output: Output,
+    /// The file to append to.
+    file: Option<File>,
+    /// Source of the time of each line.
+    clock: &'a dyn Clock,
+}
+
+impl<'a> StatusLog<'a> {
+    /// Creates a status log printing to given output, and appending to given file if any, with lines timed by given clock.
+    pub fn new(output: Output, path: Option<&str>, clock: &'a dyn Clock) -> Result<Self> {
+        let file = path
+            .map(|path| OpenOptions::new().create(true).append(true).open(path))
+            .transpose()?;
+        Ok(Self {
+            output,
+            file,
+            clock,
+        })
+    }
+
+    /// Logs a line with current time.
+    fn info(&mut self, message: &str) {
+        let now = self.clock.now().with_timezone(&CST_TIMEZONE);
+        let time = format_datetime_to_str(&now);
+        let line = format!("[{time}] {message}");
+        let record = LogRecord {
+            time: &time,
+            message,
+        };
+        if let Err(e) = self.output.stream(&record, &line) {
+            eprintln!("Failed to print status log: {e}");
+        }
+        if let Some(file) = &mut self.file
+            && let Err(e) = writeln!(file, "{line}")
+        {
+            eprintln!("Failed to write status log: {e}");
+        }
+    }
+}
+
+/// Start of the next day after given time.
+fn next_midnight(now: DateTime<FixedOffset>) -> DateTime<FixedOffset> {
+    now.date_naive()
+        .succ_opt()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .and_then(|datetime| datetime.and_local_timezone(CST_TIMEZONE).single())
+        .unwrap_or(now + Duration::days(1))
+}
+
+/// Whether two schedules contain the same classes at the same times.
+fn same_schedules(old: &[Schedule], new: &[Schedule]) -> bool {
+    old.len() == new.len()
+        && old.iter().zip(new).all(|(old, new)| {
+            old.uuid == new.uuid && old.begin_time == new.begin_time && old.end_time == new.end_time
+        })
+}
+
+/// A random delay between zero and given maximum, with millisecond granularity.
+fn random_delay(max: Duration) -> Duration {
+    let Ok(max_millis) = u64::try_from(max.num_milliseconds()) else {
+        return Duration::zero();
+    };
+    if max_millis == 0 {
+        return Duration::zero();
+    }
+    let random = RandomState::new().hash_one(time::SystemTime::now());
+    Duration::milliseconds(i64::try_from(random % (max_millis + 1)).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::output::{Format, Output},
+        MAX_ATTEMPTS, StatusLog, WatchOptions, Watcher,
+    };
+    use chrono::{DateTime, Duration, FixedOffset, TimeZone};
+    use serde_json::{Value, json};
+    use std::sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    };
+    use ucas_iclass::{
+        CheckInPolicy, FixedClock, IClass, ScheduleUuid, UserSessionInfo,
+        transport::{MemoryTransport, TransportResponse},
+        util::CST_TIMEZONE,
+    };
+
+    const UUID: &str = "0123456789abcdef0123456789abcdef";
+
+    fn at(hour: u32, min: u32) -> DateTime<FixedOffset> {
+        CST_TIMEZONE
+            .with_ymd_and_hms(2025, 10, 13, hour, min, 0)
+            .unwrap()
+    }
+
+    fn schedule(uuid: &str, begin: &str, end: &str, checked_in: bool) -> Value {
+        json!({
+            "courseNum": "C1",
+            "courseName": "Math",
+            "classroomName": "A101",
+            "teacherName": "T",
+            "id": "10",
+            "uuid": uuid,
+            "signStatus": if checked_in { "1" } else { "0" },
+            "classBeginTime": format!("2025-10-13 {begin}:00"),
+            "classEndTime": format!("2025-10-13 {end}:00"),
+        })
+    }
+
+    /// An iClass at given time, whose daily schedule is given classes, never shown as checked in, and whose check-ins fail if `fail` is set.
+    fn iclass(
+        now: DateTime<FixedOffset>,
+        schedules: Value,
+        fail: &Arc<AtomicBool>,
+    ) -> IClass<MemoryTransport> {
+        let fail = Arc::clone(fail);
+        let transport = MemoryTransport::new(move |request| {
+            let result = if request.url.path().ends_with("stu_scan_sign.action") {
+                if fail.load(Ordering::Relaxed) {
+                    return Ok(TransportResponse::json(
+                        &json!({ "STATUS": "1", "ERRMSG": "系统繁忙" }),
+                    ));
+                }
+                json!({ "stuSignId": "1", "stuSignStatus": "1" })
+            } else {
+                schedules.clone()
+            };
+            Ok(TransportResponse::json(
+                &json!({ "STATUS": "0", "result": result }),
+            ))
+        });
+        IClass::builder()
+            .user_session(UserSessionInfo {
+                id: "42".to_string(),
+                session_id: "s".to_string(),
+                real_name: "张三".to_string(),
+                student_no: "2025E8000000000".to_string(),
+            })
+            .clock(FixedClock(now.to_utc()))
+            .build_with_transport(transport)
+    }
+
+    fn watcher(iclass: &IClass<MemoryTransport>) -> Watcher<'_, MemoryTransport> {
+        let options = WatchOptions {
+            policy: CheckInPolicy::default(),
+            jitter: Duration::zero(),
+            refresh: Duration::minutes(10),
+        };
+        let log = StatusLog::new(Output::new(Format::Jsonl), None, iclass.clock()).unwrap();
+        Watcher::new(iclass, options, log, None, Arc::new(AtomicBool::new(false)))
+    }
+
+    fn check_ins(iclass: &IClass<MemoryTransport>) -> usize {
+        iclass
+            .transport()
+            .requests()
+            .iter()
+            .filter(|request| request.url.path().ends_with("stu_scan_sign.action"))
+            .count()
+    }
+
+    #[compio::test]
+    async fn plans_classes_with_open_windows() {
+        let now = at(7, 0);
+        let schedules = json!([
+            schedule(UUID, "08:00", "09:35", false),
+            schedule(&"a".repeat(32), "06:00", "06:45", false),
+            schedule(&"b".repeat(32), "10:00", "11:35", true),
+        ]);
+        let iclass = iclass(now, schedules, &Arc::default());
+        let mut watcher = watcher(&iclass);
+        watcher.refresh(now).await;
+
+        assert_eq!(watcher.pending.len(), 1);
+        let uuid = UUID.parse::<ScheduleUuid>().unwrap();
+        assert_eq!(watcher.pending[&uuid].next_attempt, at(7, 30));
+        assert_eq!(watcher.next_refresh, at(7, 10));
+        assert_eq!(watcher.due(at(7, 29)), None);
+        assert_eq!(watcher.due(at(7, 30)), Some(uuid));
+    }
+
+    #[compio::test]
+    async fn confirmation_does_not_plan_again() {
+        let now = at(7, 45);
+        let schedules = json!([schedule(UUID, "08:00", "09:35", false)]);
+        let iclass = iclass(now, schedules, &Arc::default());
+        let mut watcher = watcher(&iclass);
+        watcher.refresh(now).await;
+        let uuid = watcher.due(now).unwrap();
+
+        // The schedule still shows the class as not checked in
+        watcher.check_in(&uuid).await;
+        assert!(watcher.pending.is_empty());
+        watcher.refresh(now).await;
+        assert!(watcher.pending.is_empty());
+        assert_eq!(watcher.due(now), None);
+        assert_eq!(check_ins(&iclass), 1);
+    }
+
+    #[compio::test]
+    async fn gives_up_after_max_attempts() {
+        let now = at(7, 45);
+        let schedules = json!([schedule(UUID, "08:00", "09:35", false)]);
+        let fail = Arc::new(AtomicBool::new(true));
+        let iclass = iclass(now, schedules, &fail);
+        let mut watcher = watcher(&iclass);
+        watcher.refresh(now).await;
+        let uuid = UUID.parse::<ScheduleUuid>().unwrap();
+
+        for attempts in 1..MAX_ATTEMPTS {
+            watcher.check_in(&uuid).await;
+            assert_eq!(watcher.pending[&uuid].attempts, attempts);
+            assert_eq!(watcher.pending[&uuid].next_attempt, at(7, 46));
+        }
+        watcher.check_in(&uuid).await;
+        assert!(watcher.pending.is_empty());
+
+        fail.store(false, Ordering::Relaxed);
+        watcher.refresh(now).await;
+        assert!(watcher.pending.is_empty());
+        assert_eq!(check_ins(&iclass), MAX_ATTEMPTS as usize);
+    }
+
+    #[compio::test]
+    async fn gives_up_when_window_closes() {
+        let now = at(9, 34);
+        let schedules = json!([schedule(UUID, "08:00", "09:35", false)]);
+        let iclass = iclass(now, schedules, &Arc::new(AtomicBool::new(true)));
+        let mut watcher = watcher(&iclass);
+        watcher.refresh(now).await;
+
+        watcher
+            .check_in(&UUID.parse::<ScheduleUuid>().unwrap())
+            .await;
+        assert!(watcher.pending.is_empty());
+        watcher.refresh(now).await;
+        assert!(watcher.pending.is_empty());
+    }
+}
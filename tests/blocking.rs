@@ -0,0 +1,202 @@
+//! The blocking API against the [`MockServer`].
+
+#![cfg(all(feature = "blocking", feature = "mock"))]
+
+use chrono::{NaiveDate, TimeZone};
+use compio::runtime::Runtime;
+use std::{fmt::Debug, thread};
+use ucas_iclass::{
+    Course, IClass as AsyncIClass, IClassError, Schedule, Semester, UserSessionInfo,
+    blocking::IClass,
+    mock::{MockEndpoint, MockServer, MockState},
+    util::CST_TIMEZONE,
+};
+
+const USERNAME: &str = "13800000000";
+const PASSWORD: &str = "secret";
+const USER_ID: &str = "42";
+const UUID: &str = "0123456789abcdef0123456789abcdef";
+
+/// A user with a semester, a course and two classes on 2025-10-13.
+fn state() -> MockState {
+    let mut state = MockState::new();
+    state.add_user(
+        USERNAME,
+        PASSWORD,
+        UserSessionInfo {
+            id: USER_ID.to_string(),
+            session_id: String::new(),
+            real_name: "张三".to_string(),
+            student_no: "2025E8000000000".to_string(),
+        },
+    );
+    state.semesters.push(Semester {
+        code: "2025F".to_string(),
+        name: "2025秋".to_string(),
+        begin_date: date(2025, 9, 1),
+        end_date: date(2026, 1, 18),
+        is_current: true,
+    });
+    let course = Course {
+        course_id: "C1".to_string(),
+        course_name: "Math".to_string(),
+        classroom_name: "A101".to_string(),
+        teacher_name: "T".to_string(),
+    };
+    state.courses.push(course.clone());
+    for (id, uuid, hour) in [("10", UUID, 8), ("9", "f".repeat(32).as_str(), 10)] {
+        state.schedules.push(Schedule {
+            course: course.clone(),
+            id: id.parse().unwrap(),
+            uuid: uuid.parse().unwrap(),
+            checked_in: None,
+            begin_time: CST_TIMEZONE
+                .with_ymd_and_hms(2025, 10, 13, hour, 0, 0)
+                .unwrap(),
+            end_time: CST_TIMEZONE
+                .with_ymd_and_hms(2025, 10, 13, hour + 1, 35, 0)
+                .unwrap(),
+        });
+    }
+    state
+}
+
+fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+}
+
+fn iclass(server: &MockServer) -> IClass {
+    IClass::from_async(
+        AsyncIClass::builder()
+            .api_root(server.api_root().clone())
+            .system_proxy(false)
+            .build()
+            .unwrap(),
+    )
+    .unwrap()
+}
+
+/// Runs given requests with a fresh asynchronous instance, on a thread of its own as `compio` runtimes cannot be nested.
+fn run_async<T, F>(server: &MockServer, requests: F) -> Result<T, IClassError>
+where
+    T: Send,
+    F: AsyncFnOnce(&AsyncIClass) -> Result<T, IClassError> + Send,
+{
+    let api_root = server.api_root().clone();
+    thread::scope(|scope| {
+        scope
+            .spawn(move || {
+                let iclass = AsyncIClass::builder()
+                    .api_root(api_root)
+                    .system_proxy(false)
+                    .build()
+                    .unwrap();
+                Runtime::new().unwrap().block_on(requests(&iclass))
+            })
+            .join()
+            .unwrap()
+    })
+}
+
+/// Asserts that the blocking and asynchronous results are the same error.
+fn assert_same_error<T: Debug>(
+    blocking: Result<T, IClassError>,
+    asynchronous: Result<T, IClassError>,
+) -> IClassError {
+    let (Err(blocking), Err(asynchronous)) = (blocking, asynchronous) else {
+        panic!("expected errors");
+    };
+    assert_eq!(format!("{blocking:?}"), format!("{asynchronous:?}"));
+    blocking
+}
+
+#[test]
+fn login_and_queries() {
+    let server = MockServer::start(state()).unwrap();
+    let iclass = iclass(&server);
+    iclass.login(USERNAME, PASSWORD).unwrap();
+    assert_eq!(iclass.inner().user_session().unwrap().id, USER_ID);
+
+    let semesters = iclass.query_semester().unwrap();
+    assert_eq!(semesters.len(), 1);
+    assert_eq!(semesters[0].code, "2025F");
+
+    let courses = iclass.query_courses().unwrap();
+    assert_eq!(courses.len(), 1);
+    assert_eq!(courses[0].course_name, "Math");
+
+    let daily = iclass.query_daily_schedule(&date(2025, 10, 13)).unwrap();
+    assert_eq!(daily.len(), 2);
+
+    let weekly = iclass.query_weekly_schedule(&date(2025, 10, 15)).unwrap();
+    assert_eq!(weekly.len(), 7);
+    assert_eq!(weekly[0].date, date(2025, 10, 13));
+    assert_eq!(weekly[0].schedules.len(), 2);
+}
+
+#[test]
+fn check_in() {
+    let server = MockServer::start(state()).unwrap();
+    let iclass = iclass(&server);
+    iclass.login(USERNAME, PASSWORD).unwrap();
+    let timestamp = iclass.inner().server_timestamp_millis();
+
+    let result = iclass
+        .check_in_by_uuid(&UUID.parse().unwrap(), timestamp)
+        .unwrap();
+    assert!(result.status);
+    assert!(server.state().is_checked_in(USER_ID, UUID));
+
+    let result = iclass
+        .check_in_by_id(&"9".parse().unwrap(), timestamp)
+        .unwrap();
+    assert!(result.status);
+    assert!(server.state().is_checked_in(USER_ID, &"f".repeat(32)));
+}
+
+#[test]
+fn same_errors_as_async() {
+    let server = MockServer::start(state()).unwrap();
+    let iclass = iclass(&server);
+
+    let error = assert_same_error(
+        iclass.query_courses(),
+        run_async(&server, async |iclass| iclass.query_courses().await),
+    );
+    assert!(matches!(error, IClassError::NotLoggedIn));
+    let error = assert_same_error(
+        iclass.login(USERNAME, "wrong"),
+        run_async(&server, async |iclass| {
+            iclass.login(USERNAME, "wrong").await
+        }),
+    );
+    assert!(matches!(error, IClassError::WrongPassword(_)));
+
+    iclass.login(USERNAME, PASSWORD).unwrap();
+    let timestamp = iclass.inner().server_timestamp_millis();
+    let uuid = UUID.parse().unwrap();
+    iclass.check_in_by_uuid(&uuid, timestamp).unwrap();
+    let error = assert_same_error(
+        iclass.check_in_by_uuid(&uuid, timestamp),
+        run_async(&server, async |iclass| {
+            iclass.login(USERNAME, PASSWORD).await?;
+            iclass.check_in_by_uuid(&uuid, timestamp).await
+        }),
+    );
+    assert!(matches!(error, IClassError::AlreadyCheckedIn(_)));
+
+    for _ in 0..2 {
+        server
+            .state()
+            .inject_error(MockEndpoint::DailySchedule, Some(100), "参数错误");
+    }
+    let day = date(2025, 10, 13);
+    let error = assert_same_error(
+        iclass.query_daily_schedule(&day),
+        run_async(&server, async |iclass| {
+            iclass.login(USERNAME, PASSWORD).await?;
+            iclass.query_daily_schedule(&day).await
+        }),
+    );
+    assert!(matches!(error, IClassError::InvalidParameter(_)));
+}
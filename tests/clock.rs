@@ -0,0 +1,158 @@
+//! Time-dependent logic run at fixed times with [`FixedClock`] and a [`MemoryTransport`].
+
+use chrono::{DateTime, FixedOffset, TimeDelta, TimeZone, Utc};
+use serde_json::json;
+use ucas_iclass::{
+    CheckInPolicy, Course, FixedClock, IClass, Schedule, UserSessionInfo, ics,
+    transport::{MemoryTransport, TransportResponse},
+    util::CST_TIMEZONE,
+};
+
+fn at(hour: u32, min: u32) -> DateTime<FixedOffset> {
+    CST_TIMEZONE
+        .with_ymd_and_hms(2025, 10, 13, hour, min, 0)
+        .unwrap()
+}
+
+/// Two classes on 2025-10-13, with id 9 from 08:00 to 09:35 and id 10 from 10:00 to 11:35, the first checked in if `checked_in` is set.
+fn schedules(checked_in: bool) -> serde_json::Value {
+    let class = |id: &str, uuid: &str, begin: &str, end: &str, checked_in: bool| {
+        json!({
+            "courseNum": "C1",
+            "courseName": "Math",
+            "classroomName": "A101",
+            "teacherName": "T",
+            "id": id,
+            "uuid": uuid,
+            "signStatus": if checked_in { "1" } else { "0" },
+            "classBeginTime": format!("2025-10-13 {begin}:00"),
+            "classEndTime": format!("2025-10-13 {end}:00"),
+        })
+    };
+    json!([
+        class("9", &"a".repeat(32), "08:00", "09:35", checked_in),
+        class("10", &"b".repeat(32), "10:00", "11:35", false),
+    ])
+}
+
+/// An iClass at given time, answering schedule queries with [`schedules`], and every request with a `Date` header running given offset ahead of the system clock.
+fn iclass(
+    now: DateTime<FixedOffset>,
+    checked_in: bool,
+    offset: TimeDelta,
+) -> IClass<MemoryTransport> {
+    let transport = MemoryTransport::new(move |request| {
+        let mut response = if request.url.path().ends_with("get_stu_course_sched.action") {
+            TransportResponse::json(&json!({ "STATUS": "0", "result": schedules(checked_in) }))
+        } else {
+            TransportResponse::json(&json!({}))
+        };
+        let date = (Utc::now() + offset).format("%a, %d %b %Y %H:%M:%S GMT");
+        response
+            .headers
+            .insert("Date", date.to_string().parse().unwrap());
+        Ok(response)
+    });
+    IClass::builder()
+        .user_session(UserSessionInfo {
+            id: "42".to_string(),
+            session_id: "s".to_string(),
+            real_name: "张三".to_string(),
+            student_no: "2025E8000000000".to_string(),
+        })
+        .clock(FixedClock(now.to_utc()))
+        .build_with_transport(transport)
+}
+
+fn ids(schedules: &[Schedule]) -> Vec<&str> {
+    schedules
+        .iter()
+        .map(|schedule| schedule.id.as_str())
+        .collect()
+}
+
+#[compio::test]
+async fn current_schedules_around_window_edges() {
+    let policy = CheckInPolicy::default();
+    let current = async |now, checked_in| {
+        iclass(now, checked_in, TimeDelta::zero())
+            .current_schedules(&policy)
+            .await
+            .unwrap()
+    };
+    assert!(current(at(7, 29), false).await.is_empty());
+    assert_eq!(ids(&current(at(7, 30), false).await), ["9"]);
+    // Ranked by id in numeric order
+    assert_eq!(ids(&current(at(9, 35), false).await), ["9", "10"]);
+    assert_eq!(ids(&current(at(9, 36), false).await), ["10"]);
+    assert!(current(at(11, 36), false).await.is_empty());
+    // Classes checked in are left out, unless included, ranked last
+    assert_eq!(ids(&current(at(9, 30), true).await), ["10"]);
+    let policy = CheckInPolicy {
+        include_checked_in: true,
+        ..policy
+    };
+    let schedules = iclass(at(9, 30), true, TimeDelta::zero())
+        .current_schedules(&policy)
+        .await
+        .unwrap();
+    assert_eq!(ids(&schedules), ["10", "9"]);
+}
+
+#[compio::test]
+async fn current_schedules_by_server_clock() {
+    // Two minutes behind the server, whose clock says 07:31
+    let mut iclass = iclass(at(7, 29), false, TimeDelta::zero());
+    iclass.set_clock_offset(120_000);
+    assert_eq!(iclass.server_now(), at(7, 31));
+    let schedules = iclass
+        .current_schedules(&CheckInPolicy::default())
+        .await
+        .unwrap();
+    assert_eq!(ids(&schedules), ["9"]);
+}
+
+#[compio::test]
+async fn calibration_ignores_injected_clock() {
+    // The injected clock is years behind, but round trips are timed by the system clock
+    let mut iclass = iclass(at(8, 0), false, TimeDelta::minutes(1));
+    let offset = iclass.calibrate_clock(4).await.unwrap();
+    assert!(
+        (offset.offset_millis - 60_000).abs() <= 1_000,
+        "estimated {offset}"
+    );
+    assert_eq!(
+        iclass.server_timestamp_millis(),
+        u128::try_from(at(8, 0).timestamp_millis() + offset.offset_millis).unwrap()
+    );
+}
+
+#[test]
+fn calendar_stamped_by_clock() {
+    let schedule = Schedule {
+        course: Course {
+            course_id: "C1".to_string(),
+            course_name: "Math".to_string(),
+            classroom_name: "A101".to_string(),
+            teacher_name: "T".to_string(),
+        },
+        id: "10".parse().unwrap(),
+        uuid: "a".repeat(32).parse().unwrap(),
+        checked_in: None,
+        begin_time: at(8, 0),
+        end_time: at(9, 35),
+    };
+    let clock = FixedClock(at(7, 0).to_utc());
+    let calendar = ics::calendar([&schedule], None, &clock);
+    assert_eq!(
+        calendar,
+        ics::calendar_at([&schedule], None, &at(7, 0).to_utc())
+    );
+    assert!(calendar.contains("\r\nDTSTAMP:20251012T230000Z\r\n"));
+    assert!(calendar.contains("\r\nDTSTART;TZID=Asia/Shanghai:20251013T080000\r\n"));
+    assert!(calendar.contains("\r\nDTEND;TZID=Asia/Shanghai:20251013T093500\r\n"));
+    assert!(calendar.contains(&format!(
+        "\r\nUID:{}@iclass.ucas.edu.cn\r\n",
+        "a".repeat(32)
+    )));
+}
@@ -0,0 +1,440 @@
+//! Full flows against the [`MockServer`].
+
+use chrono::{Duration, NaiveDate, TimeZone};
+use futures_util::TryStreamExt;
+use ucas_iclass::{
+    CheckInTarget, Course, Credentials, DailySchedule, IClass, IClassError, Schedule, ScheduleUuid,
+    Semester, UserSessionInfo,
+    mock::{MockEndpoint, MockServer, MockState, SESSION_EXPIRED_MSG},
+    util::CST_TIMEZONE,
+};
+use url::Url;
+
+const USERNAME: &str = "13800000000";
+const PASSWORD: &str = "secret";
+const USER_ID: &str = "42";
+const UUID: &str = "0123456789abcdef0123456789abcdef";
+
+/// A user with a semester, a course and two classes on 2025-10-13.
+fn state() -> MockState {
+    let mut state = MockState::new();
+    state.add_user(
+        USERNAME,
+        PASSWORD,
+        UserSessionInfo {
+            id: USER_ID.to_string(),
+            session_id: String::new(),
+            real_name: "张三".to_string(),
+            student_no: "2025E8000000000".to_string(),
+        },
+    );
+    state.semesters.push(Semester {
+        code: "2025F".to_string(),
+        name: "2025秋".to_string(),
+        begin_date: date(2025, 9, 1),
+        end_date: date(2026, 1, 18),
+        is_current: true,
+    });
+    let course = Course {
+        course_id: "C1".to_string(),
+        course_name: "Math".to_string(),
+        classroom_name: "A101".to_string(),
+        teacher_name: "T".to_string(),
+    };
+    state.courses.push(course.clone());
+    for (id, uuid, hour) in [("10", UUID, 8), ("9", "f".repeat(32).as_str(), 10)] {
+        state.schedules.push(Schedule {
+            course: course.clone(),
+            id: id.parse().unwrap(),
+            uuid: uuid.parse().unwrap(),
+            checked_in: None,
+            begin_time: CST_TIMEZONE
+                .with_ymd_and_hms(2025, 10, 13, hour, 0, 0)
+                .unwrap(),
+            end_time: CST_TIMEZONE
+                .with_ymd_and_hms(2025, 10, 13, hour + 1, 35, 0)
+                .unwrap(),
+        });
+    }
+    state
+}
+
+fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+}
+
+fn iclass(server: &MockServer) -> IClass {
+    IClass::builder()
+        .api_root(server.api_root().clone())
+        .system_proxy(false)
+        .build()
+        .unwrap()
+}
+
+#[compio::test]
+async fn login() {
+    let server = MockServer::start(state()).unwrap();
+    let iclass = iclass(&server);
+    assert!(matches!(
+        iclass.query_courses().await,
+        Err(IClassError::NotLoggedIn)
+    ));
+    assert!(matches!(
+        iclass.login("nobody", PASSWORD).await,
+        Err(IClassError::UserNotFound(_))
+    ));
+    assert!(matches!(
+        iclass.login(USERNAME, "wrong").await,
+        Err(IClassError::WrongPassword(_))
+    ));
+    iclass.login(USERNAME, PASSWORD).await.unwrap();
+    let session = iclass.user_session().unwrap();
+    assert_eq!(session.id, USER_ID);
+    assert_eq!(session.real_name, "张三");
+    assert!(!session.session_id.is_empty());
+}
+
+#[compio::test]
+async fn queries() {
+    let server = MockServer::start(state()).unwrap();
+    let iclass = iclass(&server);
+    iclass.login(USERNAME, PASSWORD).await.unwrap();
+
+    let semesters = iclass.query_semester().await.unwrap();
+    assert_eq!(semesters.len(), 1);
+    assert!(semesters[0].is_current);
+    assert_eq!(semesters[0].week_of(&date(2025, 10, 13)), Some(7));
+
+    let courses = iclass.query_courses().await.unwrap();
+    assert_eq!(courses.len(), 1);
+    assert_eq!(courses[0].course_name, "Math");
+
+    let daily = iclass
+        .query_daily_schedule(&date(2025, 10, 13))
+        .await
+        .unwrap();
+    assert_eq!(daily.len(), 2);
+    assert!(daily.iter().all(|s| s.checked_in == Some(false)));
+    assert!(
+        iclass
+            .query_daily_schedule(&date(2025, 10, 12))
+            .await
+            .unwrap()
+            .is_empty()
+    );
+
+    let weekly = iclass
+        .query_weekly_schedule(&date(2025, 10, 15))
+        .await
+        .unwrap();
+    assert_eq!(weekly.len(), 7);
+    assert_eq!(weekly[0].date, date(2025, 10, 13));
+    assert_eq!(weekly[0].schedules.len(), 2);
+    assert!(weekly[1..].iter().all(|day| day.schedules.is_empty()));
+}
+
+#[compio::test]
+async fn check_in() {
+    let server = MockServer::start(state()).unwrap();
+    let iclass = iclass(&server);
+    iclass.login(USERNAME, PASSWORD).await.unwrap();
+    let timestamp = iclass.server_timestamp_millis();
+
+    let target: CheckInTarget = UUID.parse().unwrap();
+    let result = iclass.check_in(&target, timestamp).await.unwrap();
+    assert!(result.status);
+    assert!(server.state().is_checked_in(USER_ID, UUID));
+    assert!(matches!(
+        iclass.check_in(&target, timestamp).await,
+        Err(IClassError::AlreadyCheckedIn(_))
+    ));
+    // The same class by id
+    assert!(matches!(
+        iclass
+            .check_in_by_id(&"10".parse().unwrap(), timestamp)
+            .await,
+        Err(IClassError::AlreadyCheckedIn(_))
+    ));
+
+    let daily = iclass
+        .query_daily_schedule(&date(2025, 10, 13))
+        .await
+        .unwrap();
+    let checked_in: Vec<_> = daily.iter().map(|s| s.checked_in).collect();
+    assert_eq!(checked_in, [Some(true), Some(false)]);
+}
+
+#[compio::test]
+async fn check_in_status() {
+    let mut state = state();
+    // Another class on Wednesday
+    let mut wednesday = state.schedules[1].clone();
+    wednesday.id = "11".parse().unwrap();
+    wednesday.uuid = "e".repeat(32).parse().unwrap();
+    wednesday.begin_time += Duration::days(2);
+    wednesday.end_time += Duration::days(2);
+    state.schedules.push(wednesday);
+    let server = MockServer::start(state).unwrap();
+    let iclass = iclass(&server);
+    iclass.login(USERNAME, PASSWORD).await.unwrap();
+    iclass
+        .check_in_by_uuid(&UUID.parse().unwrap(), iclass.server_timestamp_millis())
+        .await
+        .unwrap();
+    let status = |weekly: &[DailySchedule]| -> Vec<_> {
+        weekly
+            .iter()
+            .flat_map(|day| &day.schedules)
+            .map(|schedule| (schedule.uuid.to_string(), schedule.checked_in))
+            .collect()
+    };
+
+    let mut weekly = iclass
+        .query_weekly_schedule(&date(2025, 10, 13))
+        .await
+        .unwrap();
+    assert_eq!(status(&weekly).len(), 3);
+    assert!(
+        status(&weekly)
+            .iter()
+            .all(|(_, checked_in)| checked_in.is_none())
+    );
+
+    server.state().requests.clear();
+    iclass.fill_check_in_status(&mut weekly).await.unwrap();
+    assert_eq!(
+        status(&weekly),
+        [
+            (UUID.to_string(), Some(true)),
+            ("f".repeat(32), Some(false)),
+            ("e".repeat(32), Some(false)),
+        ]
+    );
+    // Only the days with classes are queried
+    let requests = server.state().requests.clone();
+    assert!(
+        requests
+            .iter()
+            .all(|request| request.endpoint == MockEndpoint::DailySchedule)
+    );
+    let mut days: Vec<_> = requests
+        .iter()
+        .map(|request| request.params["dateStr"].as_str())
+        .collect();
+    days.sort_unstable();
+    assert_eq!(days, ["20251013", "20251015"]);
+}
+
+#[compio::test]
+async fn schedule_range() {
+    let mut state = state();
+    let template = state.schedules[0].clone();
+    // One class a day from Monday 2025-10-13 to Sunday 2025-11-02
+    state.schedules = (0..21)
+        .map(|day| {
+            let mut schedule = template.clone();
+            schedule.id = (100 + day).to_string().parse().unwrap();
+            schedule.uuid = format!("{day:032x}").parse().unwrap();
+            schedule.begin_time += Duration::days(day);
+            schedule.end_time += Duration::days(day);
+            schedule
+        })
+        .collect();
+    let server = MockServer::start(state).unwrap();
+    let iclass = iclass(&server);
+    iclass.login(USERNAME, PASSWORD).await.unwrap();
+    let weekly_requests = || {
+        server
+            .state()
+            .requests
+            .iter()
+            .filter(|request| request.endpoint == MockEndpoint::WeeklySchedule)
+            .count()
+    };
+
+    // Partial first and last weeks, from Wednesday to Tuesday
+    let (start, end) = (date(2025, 10, 15), date(2025, 10, 28));
+    let days: Vec<_> = iclass
+        .query_schedule_range(start, end)
+        .try_collect()
+        .await
+        .unwrap();
+    let dates: Vec<_> = days.iter().map(|day| day.date).collect();
+    let expected: Vec<_> = start.iter_days().take_while(|day| *day <= end).collect();
+    assert_eq!(dates, expected);
+    assert!(days.iter().all(|day| day.schedules.len() == 1));
+    assert_eq!(weekly_requests(), 3);
+
+    // The end date in the same week as the last weekly step
+    let days: Vec<_> = iclass
+        .query_schedule_range(date(2025, 10, 13), date(2025, 10, 22))
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(days.len(), 10);
+    assert_eq!(weekly_requests(), 3 + 2);
+}
+
+#[compio::test]
+async fn session_expiry() {
+    let server = MockServer::start(state()).unwrap();
+    let mut iclass = iclass(&server);
+    iclass.login(USERNAME, PASSWORD).await.unwrap();
+    server.state().expire_sessions();
+    assert!(matches!(
+        iclass.query_courses().await,
+        Err(IClassError::SessionExpired(_))
+    ));
+
+    iclass.set_credential_provider(Credentials {
+        username: USERNAME.to_string(),
+        password: PASSWORD.to_string(),
+    });
+    let stale = iclass.user_session().unwrap();
+    assert_eq!(iclass.query_courses().await.unwrap().len(), 1);
+    let renewed = iclass.user_session().unwrap();
+    assert_ne!(renewed.session_id, stale.session_id);
+    let logins = server
+        .state()
+        .requests
+        .iter()
+        .filter(|request| request.endpoint == MockEndpoint::Login)
+        .count();
+    assert_eq!(logins, 2);
+}
+
+#[compio::test]
+async fn login_before_first_request() {
+    let server = MockServer::start(state()).unwrap();
+    let iclass = IClass::builder()
+        .api_root(server.api_root().clone())
+        .system_proxy(false)
+        .credential_provider(Credentials {
+            username: USERNAME.to_string(),
+            password: PASSWORD.to_string(),
+        })
+        .build()
+        .unwrap();
+    assert_eq!(iclass.query_courses().await.unwrap().len(), 1);
+    assert!(iclass.user_session().is_some());
+}
+
+#[compio::test]
+async fn error_injection() {
+    let server = MockServer::start(state()).unwrap();
+    let iclass = iclass(&server);
+    iclass.login(USERNAME, PASSWORD).await.unwrap();
+    server
+        .state()
+        .inject_error(MockEndpoint::Courses, None, SESSION_EXPIRED_MSG);
+    server
+        .state()
+        .inject_error(MockEndpoint::Courses, Some(100), "参数错误");
+    server
+        .state()
+        .inject_error(MockEndpoint::Courses, None, "系统繁忙");
+    assert!(matches!(
+        iclass.query_courses().await,
+        Err(IClassError::SessionExpired(_))
+    ));
+    assert!(matches!(
+        iclass.query_courses().await,
+        Err(IClassError::InvalidParameter(_))
+    ));
+    let Err(IClassError::ApiError(info)) = iclass.query_courses().await else {
+        panic!("expected an API error");
+    };
+    assert_eq!(info.message, "系统繁忙");
+    // Injected errors are used up
+    assert!(iclass.query_courses().await.is_ok());
+}
+
+#[compio::test]
+async fn clock_skew() {
+    let mut state = state();
+    state.clock_offset_millis = 60_000;
+    state.timestamp_tolerance_millis = Some(5_000);
+    let server = MockServer::start(state).unwrap();
+    let mut iclass = iclass(&server);
+    iclass.login(USERNAME, PASSWORD).await.unwrap();
+    let uuid = UUID.parse::<ScheduleUuid>().unwrap();
+
+    assert!(matches!(
+        iclass
+            .check_in_by_uuid(&uuid, iclass.server_timestamp_millis())
+            .await,
+        Err(IClassError::QrCodeExpired(_))
+    ));
+    let offset = iclass.calibrate_clock(4).await.unwrap();
+    assert!(
+        (offset.offset_millis - 60_000).unsigned_abs() <= 1_500,
+        "estimated {offset}"
+    );
+    assert_eq!(iclass.clock_offset(), offset.offset_millis);
+    let result = iclass
+        .check_in_by_uuid(&uuid, iclass.server_timestamp_millis())
+        .await
+        .unwrap();
+    assert!(result.status);
+}
+
+/// A client for an unresolvable API root, reaching the server only as a proxy.
+fn proxied_iclass(server: &MockServer, auth: Option<(&str, &str)>) -> IClass {
+    let mut builder = IClass::builder()
+        .api_root(Url::parse("http://iclass.invalid/").unwrap())
+        .proxy(server.api_root().clone());
+    if let Some((username, password)) = auth {
+        builder = builder.proxy_auth(username, password);
+    }
+    builder.build().unwrap()
+}
+
+#[compio::test]
+async fn proxy() {
+    let mut state = state();
+    state.proxy_auth = Some(("user".to_string(), "pass".to_string()));
+    let server = MockServer::start(state).unwrap();
+    let iclass = proxied_iclass(&server, Some(("user", "pass")));
+    iclass.login(USERNAME, PASSWORD).await.unwrap();
+    assert_eq!(iclass.query_courses().await.unwrap().len(), 1);
+    let requests = server.state().requests.clone();
+    assert_eq!(requests.len(), 2);
+    assert!(requests.iter().all(|request| request.proxied));
+}
+
+#[compio::test]
+async fn proxy_auth_required() {
+    let mut state = state();
+    state.proxy_auth = Some(("user".to_string(), "pass".to_string()));
+    let server = MockServer::start(state).unwrap();
+    for auth in [None, Some(("user", "wrong"))] {
+        let iclass = proxied_iclass(&server, auth);
+        assert!(matches!(
+            iclass.login(USERNAME, PASSWORD).await,
+            Err(IClassError::ProxyAuthRequired)
+        ));
+    }
+    // Rejected before reaching the API
+    assert!(server.state().requests.is_empty());
+}
+
+#[compio::test]
+async fn no_proxy() {
+    let server = MockServer::start(state()).unwrap();
+    for (no_proxy, proxied) in [(None, true), (Some("127.0.0.1"), false)] {
+        let mut builder = IClass::builder()
+            .api_root(server.api_root().clone())
+            .proxy(server.api_root().clone());
+        if let Some(no_proxy) = no_proxy {
+            builder = builder.no_proxy(no_proxy);
+        }
+        builder
+            .build()
+            .unwrap()
+            .login(USERNAME, PASSWORD)
+            .await
+            .unwrap();
+        let request = server.state().requests.pop().unwrap();
+        assert_eq!(request.proxied, proxied, "no_proxy: {no_proxy:?}");
+    }
+}
@@ -0,0 +1,98 @@
+//! Decoding check-in QR codes from the images in `tests/fixtures/qr`.
+//!
+//! The fixtures are QR codes rendered at a few module sizes, then rotated, skewed in perspective, blurred by supersampling, shaded by a gradient, and sprinkled with noise or flipped modules, saved as PNG or JPEG:
+//!
+//! - `screenshot.png`: a clean screenshot, 2 px per module.
+//! - `photo-skewed.jpg`: a photo taken at an angle, rotated by 20° with perspective and noise.
+//! - `photo-rotated.jpg`: a slightly rotated noisy photo.
+//! - `damaged.png`: error correction level H with 25 flipped modules.
+//! - `hash-route.png`: parameters in the fragment of the URL, with no timestamp.
+//! - `invalid-uuid.png`: an iClass-like URL with a malformed uuid.
+//! - `other.png`: a QR code of `hello world`.
+
+#![cfg(feature = "qr-image")]
+
+use std::{fs, io::Cursor};
+use ucas_iclass::{CheckInTarget, IClassError, QrPayload};
+
+const UUID: &str = "0123456789abcdef0123456789abcdef";
+const TIMESTAMP: Option<u128> = Some(1_760_000_000_000);
+
+fn decode(name: &str) -> Result<QrPayload, IClassError> {
+    let path = format!("{}/tests/fixtures/qr/{name}", env!("CARGO_MANIFEST_DIR"));
+    QrPayload::from_image(&fs::read(path).unwrap())
+}
+
+fn error_message(result: Result<QrPayload, IClassError>) -> String {
+    match result {
+        Err(IClassError::QrImageError(message)) => message,
+        other => panic!("expected a QR image error, got {other:?}"),
+    }
+}
+
+#[test]
+fn screenshot() {
+    let payload = decode("screenshot.png").unwrap();
+    assert_eq!(payload.target, CheckInTarget::Uuid(UUID.parse().unwrap()));
+    assert_eq!(payload.timestamp, TIMESTAMP);
+}
+
+#[test]
+fn skewed_photo() {
+    // The timestamp in seconds is converted
+    let payload = decode("photo-skewed.jpg").unwrap();
+    assert_eq!(payload.target, CheckInTarget::Id("123456".parse().unwrap()));
+    assert_eq!(payload.timestamp, TIMESTAMP);
+}
+
+#[test]
+fn rotated_photo() {
+    let payload = decode("photo-rotated.jpg").unwrap();
+    assert_eq!(payload.target, CheckInTarget::Uuid(UUID.parse().unwrap()));
+    assert_eq!(payload.timestamp, TIMESTAMP);
+}
+
+#[test]
+fn damaged() {
+    let payload = decode("damaged.png").unwrap();
+    assert_eq!(payload.target, CheckInTarget::Uuid(UUID.parse().unwrap()));
+    assert_eq!(payload.timestamp, TIMESTAMP);
+}
+
+#[test]
+fn hash_route() {
+    let payload = decode("hash-route.png").unwrap();
+    assert_eq!(
+        payload.target,
+        CheckInTarget::Uuid("f".repeat(32).parse().unwrap())
+    );
+    assert_eq!(payload.timestamp, None);
+}
+
+#[test]
+fn not_iclass() {
+    assert_eq!(
+        error_message(decode("other.png")),
+        "no iClass check-in QR code found, only: hello world"
+    );
+    let message = error_message(decode("invalid-uuid.png"));
+    assert!(
+        message.starts_with(
+            "no iClass check-in QR code found, only: https://iclass.ucas.edu.cn/?timeTableId=abc&pad=zzz"
+        ),
+        "{message}"
+    );
+}
+
+#[test]
+fn no_qr_code() {
+    let mut blank = Vec::new();
+    image::GrayImage::from_pixel(200, 200, image::Luma([255]))
+        .write_to(&mut Cursor::new(&mut blank), image::ImageFormat::Png)
+        .unwrap();
+    assert_eq!(
+        error_message(QrPayload::from_image(&blank)),
+        "no QR code found"
+    );
+    assert!(error_message(QrPayload::from_image(b"not an image")).starts_with("cannot read image"));
+}
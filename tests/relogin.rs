@@ -0,0 +1,114 @@
+//! Logging in again when concurrent requests find the session expired.
+
+use futures_util::future::join;
+use serde_json::json;
+use std::{
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+use ucas_iclass::{
+    Credentials, IClass, IClassError, UserSessionInfo,
+    transport::{MemoryTransport, Transport, TransportRequest, TransportResponse},
+};
+
+/// A [`MemoryTransport`] yielding once before answering, so that concurrent requests interleave.
+struct YieldingTransport(MemoryTransport);
+
+impl Transport for YieldingTransport {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse, IClassError> {
+        YieldNow(false).await;
+        self.0.send(request).await
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        self.0.sleep(duration).await;
+    }
+}
+
+/// Returns pending once, waking itself.
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            Poll::Ready(())
+        } else {
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+/// Session ids are `s1`, `s2`, ... in order of logins, and only the latest one is valid.
+fn transport(logins: Arc<AtomicUsize>) -> YieldingTransport {
+    YieldingTransport(MemoryTransport::new(move |request| {
+        if request.url.path().ends_with("login.action") {
+            let n = logins.fetch_add(1, Ordering::SeqCst) + 1;
+            return Ok(TransportResponse::json(&json!({
+                "STATUS": "0",
+                "result": {
+                    "id": "42",
+                    "sessionId": format!("s{n}"),
+                    "realName": "张三",
+                    "studentNo": "2025E8000000000",
+                },
+            })));
+        }
+        let latest = format!("s{}", logins.load(Ordering::SeqCst));
+        let valid = request
+            .headers
+            .iter()
+            .any(|(name, value)| name == "sessionId" && *value == latest);
+        Ok(TransportResponse::json(&if valid {
+            json!({ "STATUS": "0", "result": [] })
+        } else {
+            json!({ "STATUS": "1", "ERRMSG": "用户未登录" })
+        }))
+    }))
+}
+
+fn iclass(logins: &Arc<AtomicUsize>) -> IClass<YieldingTransport> {
+    IClass::builder()
+        .credential_provider(Credentials {
+            username: "13800000000".to_string(),
+            password: "secret".to_string(),
+        })
+        .build_with_transport(transport(Arc::clone(logins)))
+}
+
+#[compio::test]
+async fn expired_session_renewed_once() {
+    let logins = Arc::new(AtomicUsize::new(0));
+    let iclass = iclass(&logins);
+    iclass.set_user_session(Some(UserSessionInfo {
+        id: "42".to_string(),
+        session_id: "expired".to_string(),
+        real_name: "张三".to_string(),
+        student_no: "2025E8000000000".to_string(),
+    }));
+
+    let (first, second) = join(iclass.query_courses(), iclass.query_courses()).await;
+    assert!(first.unwrap().is_empty());
+    assert!(second.unwrap().is_empty());
+    assert_eq!(logins.load(Ordering::SeqCst), 1);
+    assert_eq!(iclass.user_session().unwrap().session_id, "s1");
+}
+
+#[compio::test]
+async fn first_login_runs_once() {
+    let logins = Arc::new(AtomicUsize::new(0));
+    let iclass = iclass(&logins);
+
+    let (first, second) = join(iclass.query_courses(), iclass.query_courses()).await;
+    assert!(first.unwrap().is_empty());
+    assert!(second.unwrap().is_empty());
+    assert_eq!(logins.load(Ordering::SeqCst), 1);
+}
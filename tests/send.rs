@@ -0,0 +1,60 @@
+//! Futures of [`IClass`] with [`ReqwestTransport`] can be spawned on a multi-threaded `tokio` runtime.
+
+#![cfg(feature = "reqwest")]
+
+use std::sync::Arc;
+use ucas_iclass::{
+    Credentials, IClass, UserSessionInfo,
+    mock::{MockServer, MockState},
+    transport::ReqwestTransport,
+};
+
+const USERNAME: &str = "13800000000";
+const PASSWORD: &str = "secret";
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn spawn_on_multi_thread_runtime() {
+    let mut state = MockState::new();
+    state.add_user(
+        USERNAME,
+        PASSWORD,
+        UserSessionInfo {
+            id: "42".to_string(),
+            session_id: String::new(),
+            real_name: "张三".to_string(),
+            student_no: "2025E8000000000".to_string(),
+        },
+    );
+    let server = MockServer::start(state).unwrap();
+    let mut iclass: IClass<ReqwestTransport> = IClass::builder()
+        .api_root(server.api_root().clone())
+        .system_proxy(false)
+        .credential_provider(Credentials {
+            username: USERNAME.to_string(),
+            password: PASSWORD.to_string(),
+        })
+        .build_with()
+        .unwrap();
+    iclass.calibrate_clock(1).await.unwrap();
+    let iclass = Arc::new(iclass);
+
+    let tasks: Vec<_> = (0..4)
+        .map(|_| {
+            let iclass = Arc::clone(&iclass);
+            tokio::spawn(async move {
+                iclass.query_courses().await?;
+                iclass.query_semester().await?;
+                let target = "0123456789abcdef0123456789abcdef".parse().unwrap();
+                // No such class in the mock, but the future is spawned all the same
+                let _ = iclass
+                    .check_in(&target, iclass.server_timestamp_millis())
+                    .await;
+                Ok::<_, ucas_iclass::IClassError>(())
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap().unwrap();
+    }
+    assert!(iclass.user_session().is_some());
+}